@@ -0,0 +1,155 @@
+//! Generic helpers for 64-bit RISC-V harts.
+//!
+//! On other architectures the functions compile to portable fallbacks, so
+//! code using them can still be built and tested on the host.
+#[cfg(target_arch = "riscv64")]
+use core::arch::{asm, global_asm};
+
+pub mod sbi;
+pub mod trap;
+
+/// Stop this hart forever.
+pub fn hang() -> ! {
+    loop {
+        wfi();
+    }
+}
+
+/// Wait for an interrupt. Spurious wakeups are allowed, so callers have to
+/// check their condition again afterwards.
+#[inline(always)]
+pub fn wfi() {
+    #[cfg(target_arch = "riscv64")]
+    unsafe {
+        asm!("wfi", options(nomem, nostack))
+    };
+    #[cfg(not(target_arch = "riscv64"))]
+    core::hint::spin_loop();
+}
+
+/// Order all earlier memory accesses before all later ones, including
+/// MMIO, as seen by other harts and devices.
+#[inline(always)]
+pub fn fence() {
+    #[cfg(target_arch = "riscv64")]
+    unsafe {
+        asm!("fence iorw, iorw", options(nostack))
+    };
+    #[cfg(not(target_arch = "riscv64"))]
+    core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+}
+
+/// Make instruction fetches on this hart see earlier stores, e.g. after
+/// copying code to RAM. There is no architectural D-cache flush; other harts
+/// need to run this themselves.
+#[inline(always)]
+pub fn fence_i() {
+    #[cfg(target_arch = "riscv64")]
+    unsafe {
+        asm!("fence.i", options(nostack))
+    };
+    #[cfg(not(target_arch = "riscv64"))]
+    core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+}
+
+/// Flush this hart's address translation caches.
+#[inline(always)]
+pub fn sfence_vma() {
+    #[cfg(target_arch = "riscv64")]
+    unsafe {
+        asm!("sfence.vma", options(nostack))
+    };
+    #[cfg(not(target_arch = "riscv64"))]
+    core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+}
+
+/// Registers saved by `setjmp`: everything the psABI requires a callee to
+/// preserve, except for the floating point registers, which oreboot does
+/// not use.
+///
+/// The layout is fixed, the assembly below depends on it:
+/// `ra` at offset 0, `sp` at 8, then `s0` to `s11` from offset 16.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub struct JmpBuf {
+    pub ra: usize,
+    pub sp: usize,
+    pub s: [usize; 12],
+}
+
+pub const JMP_BUF_SIZE: usize = 14 * 8;
+const _: () = assert!(core::mem::size_of::<JmpBuf>() == JMP_BUF_SIZE);
+
+#[cfg(target_arch = "riscv64")]
+extern "C" {
+    /// Save the current context into `buf` and return 0. A later `longjmp`
+    /// on the same `buf` returns here again, with its `val`.
+    ///
+    /// # Safety
+    /// This returns twice, which Rust does not know about. The function
+    /// that called `setjmp` must not have returned before the `longjmp`,
+    /// and no frames with destructors may be jumped over.
+    pub fn setjmp(buf: *mut JmpBuf) -> usize;
+    /// Restore the context saved in `buf`, making its `setjmp` return `val`,
+    /// or 1 if `val` is 0.
+    ///
+    /// # Safety
+    /// `buf` must have been filled by `setjmp`, see there.
+    pub fn longjmp(buf: *const JmpBuf, val: usize) -> !;
+}
+
+#[cfg(target_arch = "riscv64")]
+global_asm!(
+    ".section .text.setjmp",
+    ".global setjmp",
+    "setjmp:",
+    "sd ra, 0(a0)",
+    "sd sp, 8(a0)",
+    "sd s0, 16(a0)",
+    "sd s1, 24(a0)",
+    "sd s2, 32(a0)",
+    "sd s3, 40(a0)",
+    "sd s4, 48(a0)",
+    "sd s5, 56(a0)",
+    "sd s6, 64(a0)",
+    "sd s7, 72(a0)",
+    "sd s8, 80(a0)",
+    "sd s9, 88(a0)",
+    "sd s10, 96(a0)",
+    "sd s11, 104(a0)",
+    "li a0, 0",
+    "ret",
+    ".section .text.longjmp",
+    ".global longjmp",
+    "longjmp:",
+    "ld ra, 0(a0)",
+    "ld sp, 8(a0)",
+    "ld s0, 16(a0)",
+    "ld s1, 24(a0)",
+    "ld s2, 32(a0)",
+    "ld s3, 40(a0)",
+    "ld s4, 48(a0)",
+    "ld s5, 56(a0)",
+    "ld s6, 64(a0)",
+    "ld s7, 72(a0)",
+    "ld s8, 80(a0)",
+    "ld s9, 88(a0)",
+    "ld s10, 96(a0)",
+    "ld s11, 104(a0)",
+    // return val, but never 0
+    "seqz a0, a1",
+    "add a0, a0, a1",
+    "ret",
+);
+
+#[test]
+fn jmp_buf_layout() {
+    let buf = JmpBuf::default();
+    let base = &buf as *const _ as usize;
+    let offset = |field: *const usize| field as usize - base;
+    assert_eq!(core::mem::size_of::<JmpBuf>(), JMP_BUF_SIZE);
+    assert_eq!(offset(&buf.ra), 0);
+    assert_eq!(offset(&buf.sp), 8);
+    assert_eq!(offset(&buf.s[0]), 16);
+    assert_eq!(offset(&buf.s[11]), 104);
+}
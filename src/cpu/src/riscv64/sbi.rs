@@ -0,0 +1,139 @@
+//! Calls into the Supervisor Binary Interface, see
+//! https://github.com/riscv-non-isa/riscv-sbi-doc
+//!
+//! Register ABI: the extension ID goes in `a7`, the function ID in `a6`
+//! and arguments in `a0` to `a5`. After `ecall`, `a0` holds the error code
+//! and `a1` the return value. All other registers are preserved.
+//!
+//! On other architectures, every call fails with `NotSupported`.
+#[cfg(target_arch = "riscv64")]
+use core::arch::asm;
+
+// legacy console extension, there is no replacement with the same semantics
+const EID_CONSOLE_PUTCHAR: usize = 0x01;
+const EID_TIME: usize = 0x5449_4d45;
+const EID_SRST: usize = 0x5352_5354;
+
+/// Raw return value of an SBI call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SbiRet {
+    pub error: isize,
+    pub value: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbiError {
+    Failed,
+    NotSupported,
+    InvalidParam,
+    Denied,
+    InvalidAddress,
+    AlreadyAvailable,
+    AlreadyStarted,
+    AlreadyStopped,
+    /// Any code not defined by the specification
+    Unknown(isize),
+}
+
+impl SbiRet {
+    /// Split into the value on success or the error code.
+    pub fn result(self) -> Result<usize, SbiError> {
+        match self.error {
+            0 => Ok(self.value),
+            -1 => Err(SbiError::Failed),
+            -2 => Err(SbiError::NotSupported),
+            -3 => Err(SbiError::InvalidParam),
+            -4 => Err(SbiError::Denied),
+            -5 => Err(SbiError::InvalidAddress),
+            -6 => Err(SbiError::AlreadyAvailable),
+            -7 => Err(SbiError::AlreadyStarted),
+            -8 => Err(SbiError::AlreadyStopped),
+            e => Err(SbiError::Unknown(e)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum ResetType {
+    Shutdown = 0,
+    ColdReboot = 1,
+    WarmReboot = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum ResetReason {
+    NoReason = 0,
+    SystemFailure = 1,
+}
+
+/// Issue an `ecall` to the SBI implementation.
+#[cfg(target_arch = "riscv64")]
+pub fn sbi_call(eid: usize, fid: usize, args: [usize; 6]) -> SbiRet {
+    let (error, value);
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("a0") args[0] => error,
+            inlateout("a1") args[1] => value,
+            in("a2") args[2],
+            in("a3") args[3],
+            in("a4") args[4],
+            in("a5") args[5],
+            in("a6") fid,
+            in("a7") eid,
+            options(nostack),
+        );
+    }
+    SbiRet { error, value }
+}
+
+#[cfg(not(target_arch = "riscv64"))]
+pub fn sbi_call(_eid: usize, _fid: usize, _args: [usize; 6]) -> SbiRet {
+    SbiRet {
+        error: -2,
+        value: 0,
+    }
+}
+
+/// Write a byte to the debug console, blocking until it is sent.
+pub fn console_putchar(c: u8) {
+    // legacy calls only return an error code in a0
+    sbi_call(EID_CONSOLE_PUTCHAR, 0, [c as usize, 0, 0, 0, 0, 0]);
+}
+
+/// Program the next timer interrupt for this hart, in `time` ticks.
+pub fn set_timer(stime: u64) -> Result<(), SbiError> {
+    sbi_call(EID_TIME, 0, [stime as usize, 0, 0, 0, 0, 0])
+        .result()
+        .map(|_| ())
+}
+
+/// Reset or shut down the system. This only returns on failure.
+pub fn system_reset(reset_type: ResetType, reason: ResetReason) -> SbiError {
+    let ret = sbi_call(
+        EID_SRST,
+        0,
+        [reset_type as usize, reason as usize, 0, 0, 0, 0],
+    );
+    match ret.result() {
+        Ok(_) => SbiError::Failed,
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn sbi_ret_result() {
+    let ret = |error, value| SbiRet { error, value };
+    assert_eq!(ret(0, 42).result(), Ok(42));
+    assert_eq!(ret(-1, 0).result(), Err(SbiError::Failed));
+    assert_eq!(ret(-2, 0).result(), Err(SbiError::NotSupported));
+    assert_eq!(ret(-3, 0).result(), Err(SbiError::InvalidParam));
+    assert_eq!(ret(-8, 7).result(), Err(SbiError::AlreadyStopped));
+    assert_eq!(ret(-100, 0).result(), Err(SbiError::Unknown(-100)));
+    assert_eq!(
+        system_reset(ResetType::ColdReboot, ResetReason::NoReason),
+        SbiError::NotSupported
+    );
+}
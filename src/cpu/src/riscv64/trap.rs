@@ -0,0 +1,269 @@
+//! Minimal trap handling for bring-up: dump the state and halt.
+//!
+//! The entry points switch to a dedicated trap stack, so that faults caused by
+//! a bad or overflowed `sp` can be reported too. They save all integer
+//! registers and the trap CSRs into a `TrapFrame` on it, then call
+//! `oreboot_trap_handler`, which prints the cause, the faulting PC and the
+//! registers, and hangs.
+//! There is no way back; this is for diagnosing faults, not handling them.
+#[cfg(target_arch = "riscv64")]
+use core::arch::{asm, global_asm};
+#[cfg(target_arch = "riscv64")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Integer registers and trap CSRs at the time of the trap.
+/// `regs[n]` holds `xn`; `regs[0]` is always 0 and `regs[2]` is the `sp` of
+/// the trapped code.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub struct TrapFrame {
+    pub regs: [usize; 32],
+    /// `mcause` or `scause`
+    pub cause: usize,
+    /// `mepc` or `sepc`
+    pub epc: usize,
+    /// `mtval` or `stval`
+    pub tval: usize,
+}
+
+// The assembly below depends on this layout. The stack frame is rounded up
+// to keep sp 16 byte aligned.
+pub const TRAP_FRAME_SIZE: usize = 35 * 8;
+const _: () = assert!(core::mem::size_of::<TrapFrame>() == TRAP_FRAME_SIZE);
+
+// One trap stack per hart, handed out by `set_trap_vector`. The handler only
+// formats and prints, which fits easily.
+#[cfg(target_arch = "riscv64")]
+const TRAP_STACK_SIZE: usize = 2048;
+#[cfg(target_arch = "riscv64")]
+const TRAP_STACKS: usize = 5;
+#[cfg(target_arch = "riscv64")]
+const _: () = assert!(TRAP_STACK_SIZE % 16 == 0);
+
+#[cfg(target_arch = "riscv64")]
+#[repr(C, align(16))]
+struct TrapStacks([[u8; TRAP_STACK_SIZE]; TRAP_STACKS]);
+
+#[cfg(target_arch = "riscv64")]
+static mut TRAP_STACK: TrapStacks = TrapStacks([[0; TRAP_STACK_SIZE]; TRAP_STACKS]);
+#[cfg(target_arch = "riscv64")]
+static NEXT_TRAP_STACK: AtomicUsize = AtomicUsize::new(0);
+
+/// Decoded `xcause` register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trap {
+    Interrupt(usize),
+    Exception(usize),
+}
+
+impl Trap {
+    /// The most significant bit tells interrupts from exceptions; the
+    /// remaining bits are the cause code.
+    pub fn from_cause(cause: usize) -> Self {
+        const INTERRUPT: usize = 1 << (usize::BITS - 1);
+        if cause & INTERRUPT != 0 {
+            Trap::Interrupt(cause & !INTERRUPT)
+        } else {
+            Trap::Exception(cause)
+        }
+    }
+
+    /// See the privileged spec, table "Machine cause register values".
+    pub fn description(self) -> &'static str {
+        match self {
+            Trap::Interrupt(1) => "supervisor software interrupt",
+            Trap::Interrupt(3) => "machine software interrupt",
+            Trap::Interrupt(5) => "supervisor timer interrupt",
+            Trap::Interrupt(7) => "machine timer interrupt",
+            Trap::Interrupt(9) => "supervisor external interrupt",
+            Trap::Interrupt(11) => "machine external interrupt",
+            Trap::Interrupt(_) => "unknown interrupt",
+            Trap::Exception(0) => "instruction address misaligned",
+            Trap::Exception(1) => "instruction access fault",
+            Trap::Exception(2) => "illegal instruction",
+            Trap::Exception(3) => "breakpoint",
+            Trap::Exception(4) => "load address misaligned",
+            Trap::Exception(5) => "load access fault",
+            Trap::Exception(6) => "store/AMO address misaligned",
+            Trap::Exception(7) => "store/AMO access fault",
+            Trap::Exception(8) => "environment call from U-mode",
+            Trap::Exception(9) => "environment call from S-mode",
+            Trap::Exception(11) => "environment call from M-mode",
+            Trap::Exception(12) => "instruction page fault",
+            Trap::Exception(13) => "load page fault",
+            Trap::Exception(15) => "store/AMO page fault",
+            Trap::Exception(_) => "unknown exception",
+        }
+    }
+}
+
+/// Privilege mode to take traps in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Machine,
+    Supervisor,
+}
+
+/// Point `mtvec` or `stvec` at the matching trap entry, in direct mode, and
+/// `mscratch` or `sscratch` at a trap stack for the calling hart.
+/// Call it once on each hart; harts beyond the number of trap stacks share
+/// the last one.
+#[cfg(target_arch = "riscv64")]
+pub fn set_trap_vector(mode: Mode) {
+    extern "C" {
+        fn oreboot_trap_entry_m();
+        fn oreboot_trap_entry_s();
+    }
+    let slot = NEXT_TRAP_STACK
+        .fetch_add(1, Ordering::Relaxed)
+        .min(TRAP_STACKS - 1);
+    let stack = unsafe { core::ptr::addr_of_mut!(TRAP_STACK.0[slot]) } as usize;
+    let top = stack + TRAP_STACK_SIZE;
+    unsafe {
+        match mode {
+            Mode::Machine => {
+                asm!("csrw mscratch, {}", in(reg) top);
+                asm!("csrw mtvec, {}", in(reg) oreboot_trap_entry_m as usize);
+            }
+            Mode::Supervisor => {
+                asm!("csrw sscratch, {}", in(reg) top);
+                asm!("csrw stvec, {}", in(reg) oreboot_trap_entry_s as usize);
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "riscv64")]
+#[no_mangle]
+extern "C" fn oreboot_trap_handler(frame: &TrapFrame) -> ! {
+    let trap = Trap::from_cause(frame.cause);
+    log::println!(
+        "[oreboot] trap: {} ({trap:?}) at pc {:#x}, tval {:#x}",
+        trap.description(),
+        frame.epc,
+        frame.tval
+    );
+    for (i, r) in frame.regs.chunks(4).enumerate() {
+        let n = 4 * i;
+        log::println!(
+            "x{n:<2} {:016x}  x{:<2} {:016x}  x{:<2} {:016x}  x{:<2} {:016x}",
+            r[0],
+            n + 1,
+            r[1],
+            n + 2,
+            r[2],
+            n + 3,
+            r[3]
+        );
+    }
+    super::hang()
+}
+
+// Switch to the trap stack held in the given scratch CSR and push a
+// TrapFrame, saving x1 and x3-x31. The scratch CSR is left holding the
+// trapped code's sp, which is saved as x2.
+#[cfg(target_arch = "riscv64")]
+macro_rules! save_regs {
+    ($scratch:literal) => {
+        concat!(
+            "
+        csrrw sp, ",
+            $scratch,
+            ", sp
+        addi sp, sp, -288
+        sd x1, 8(sp)
+        sd x3, 24(sp)
+        sd x4, 32(sp)
+        sd x5, 40(sp)
+        sd x6, 48(sp)
+        sd x7, 56(sp)
+        sd x8, 64(sp)
+        sd x9, 72(sp)
+        sd x10, 80(sp)
+        sd x11, 88(sp)
+        sd x12, 96(sp)
+        sd x13, 104(sp)
+        sd x14, 112(sp)
+        sd x15, 120(sp)
+        sd x16, 128(sp)
+        sd x17, 136(sp)
+        sd x18, 144(sp)
+        sd x19, 152(sp)
+        sd x20, 160(sp)
+        sd x21, 168(sp)
+        sd x22, 176(sp)
+        sd x23, 184(sp)
+        sd x24, 192(sp)
+        sd x25, 200(sp)
+        sd x26, 208(sp)
+        sd x27, 216(sp)
+        sd x28, 224(sp)
+        sd x29, 232(sp)
+        sd x30, 240(sp)
+        sd x31, 248(sp)
+        sd x0, 0(sp)
+        csrr t0, ",
+            $scratch,
+            "
+        sd t0, 16(sp)
+        "
+        )
+    };
+}
+
+#[cfg(target_arch = "riscv64")]
+global_asm!(concat!(
+    "
+    .section .text.trap
+    .balign 4
+    .global oreboot_trap_entry_m
+oreboot_trap_entry_m:",
+    save_regs!("mscratch"),
+    "
+    csrr t0, mcause
+    sd t0, 256(sp)
+    csrr t0, mepc
+    sd t0, 264(sp)
+    csrr t0, mtval
+    sd t0, 272(sp)
+    mv a0, sp
+    call oreboot_trap_handler
+
+    .balign 4
+    .global oreboot_trap_entry_s
+oreboot_trap_entry_s:",
+    save_regs!("sscratch"),
+    "
+    csrr t0, scause
+    sd t0, 256(sp)
+    csrr t0, sepc
+    sd t0, 264(sp)
+    csrr t0, stval
+    sd t0, 272(sp)
+    mv a0, sp
+    call oreboot_trap_handler
+    "
+));
+
+#[test]
+fn decode_cause() {
+    let interrupt = 1 << (usize::BITS - 1);
+    assert_eq!(Trap::from_cause(2), Trap::Exception(2));
+    assert_eq!(Trap::from_cause(interrupt | 7), Trap::Interrupt(7));
+    assert_eq!(Trap::from_cause(2).description(), "illegal instruction");
+    assert_eq!(
+        Trap::from_cause(interrupt | 7).description(),
+        "machine timer interrupt"
+    );
+    assert_eq!(
+        Trap::from_cause(interrupt | 11).description(),
+        "machine external interrupt"
+    );
+    assert_eq!(Trap::from_cause(13).description(), "load page fault");
+    // reserved codes
+    assert_eq!(Trap::from_cause(10).description(), "unknown exception");
+    assert_eq!(
+        Trap::from_cause(interrupt | 2).description(),
+        "unknown interrupt"
+    );
+}
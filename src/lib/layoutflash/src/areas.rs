@@ -27,6 +27,7 @@ impl<'a, 'b> Iterator for FdtIterator<'a, 'b> {
 #[derive(Clone, Debug, PartialEq)]
 pub struct Area<'a> {
     pub name: &'a str,
+    pub description: Option<&'a str>,
     pub offset: Option<usize>,
     pub size: usize,
     pub file: Option<&'a str>,
@@ -59,17 +60,22 @@ pub fn create_areas<'a>(fdt: &'a fdt::Fdt<'a>, areas: &'a mut [Area<'a>]) -> &'a
         for child in node.children() {
             let mut a: Area<'a> = Area {
                 name: child.name,
+                description: None,
                 offset: None,
                 size: 0,
                 file: None,
             };
             for p in child.properties() {
                 // There can be all kinds of properties in a node.
-                // we only care about file, size, and offset.
+                // we only care about file, size, and offset, plus the
+                // description to look areas up by.
                 // Not that we remove any, just that those relate
                 // to data we put in the image.
 
                 match p.name {
+                    "description" => {
+                        a.description = p.as_str();
+                    }
                     "file" => {
                         a.file = Some(p.as_str().expect("MISSING NAME"));
                     }
@@ -90,6 +96,76 @@ pub fn create_areas<'a>(fdt: &'a fdt::Fdt<'a>, areas: &'a mut [Area<'a>]) -> &'a
     areas
 }
 
+/// Look up an area by its node name, e.g. `area@1`. Node names are numbered
+/// in DT order, so they change when areas are added or reordered; prefer
+/// `find_area_by_description` to find a particular area.
+pub fn find_area_by_name<'a, 'b>(areas: &'b [Area<'a>], name: &str) -> Option<&'b Area<'a>> {
+    areas.iter().find(|a| a.name == name)
+}
+
+/// Look up an area by its `description` property, e.g. `header`. Unlike node
+/// names, descriptions do not change when areas are added or reordered.
+/// Returns the area along with its offset as `layout_flash` places it: an area
+/// without an offset follows the previous one.
+pub fn find_area_by_description<'a, 'b>(
+    areas: &'b [Area<'a>],
+    description: &str,
+) -> Option<(usize, &'b Area<'a>)> {
+    let mut last_area_end = 0;
+    for a in areas {
+        let offset = a.offset.unwrap_or(last_area_end);
+        if a.description == Some(description) {
+            return Some((offset, a));
+        }
+        last_area_end = offset + a.size;
+    }
+    None
+}
+
+#[test]
+fn find_area() {
+    let areas = [
+        Area {
+            name: "area@0",
+            description: Some("header"),
+            offset: None,
+            size: 0x400,
+            file: None,
+        },
+        Area {
+            name: "area@1",
+            description: Some("bt0"),
+            offset: None,
+            size: 0x10000,
+            file: Some("bt0.bin"),
+        },
+        Area {
+            name: "area@2",
+            description: Some("main"),
+            offset: Some(0x20000),
+            size: 0x10000,
+            file: Some("main.bin"),
+        },
+    ];
+    assert_eq!(
+        find_area_by_description(&areas, "header"),
+        Some((0, &areas[0]))
+    );
+    assert_eq!(
+        find_area_by_description(&areas, "bt0"),
+        Some((0x400, &areas[1]))
+    );
+    assert_eq!(
+        find_area_by_description(&areas, "main"),
+        Some((0x20000, &areas[2]))
+    );
+    assert_eq!(find_area_by_description(&areas, "area@1"), None);
+    assert_eq!(find_area_by_description(&[], "header"), None);
+    assert_eq!(find_area_by_name(&areas, "area@1"), Some(&areas[1]));
+    assert_eq!(find_area_by_name(&areas, "header"), None);
+    assert_eq!(find_area_by_name(&[], "area@0"), None);
+}
+
 #[test]
 fn read_create() {
     static DATA: &'static [u8] = include_bytes!("testdata/test.dtb");
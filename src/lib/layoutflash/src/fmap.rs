@@ -0,0 +1,215 @@
+// Parser for coreboot style flashmaps, see
+// https://github.com/coreboot/coreboot/blob/main/util/cbfstool/flashmap/fmap.h
+// All values are little endian and the structs are packed.
+use core::option::Option;
+use core::result::Result;
+use core::result::Result::Err;
+use core::result::Result::Ok;
+
+use crate::areas::Area;
+
+pub const FMAP_SIGNATURE: &[u8; 8] = b"__FMAP__";
+pub const FMAP_VER_MAJOR: u8 = 1;
+const FMAP_STRLEN: usize = 32;
+
+// signature, ver_major, ver_minor, base (u64), size (u32), name, nareas (u16)
+const FMAP_HEADER_SIZE: usize = 8 + 1 + 1 + 8 + 4 + FMAP_STRLEN + 2;
+// offset (u32), size (u32), name, flags (u16)
+const FMAP_AREA_SIZE: usize = 4 + 4 + FMAP_STRLEN + 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FmapError {
+    BadSignature,
+    UnsupportedVersion(u8, u8),
+    Truncated,
+    BadName,
+}
+
+#[derive(Debug)]
+pub struct Fmap<'a> {
+    pub ver_major: u8,
+    pub ver_minor: u8,
+    /// Address of the flash in the memory map, if any
+    pub base: u64,
+    pub size: u32,
+    pub name: &'a str,
+    areas: &'a [u8],
+}
+
+fn u16_at(d: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([d[pos], d[pos + 1]])
+}
+
+fn u32_at(d: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([d[pos], d[pos + 1], d[pos + 2], d[pos + 3]])
+}
+
+fn u64_at(d: &[u8], pos: usize) -> u64 {
+    (u32_at(d, pos + 4) as u64) << 32 | u32_at(d, pos) as u64
+}
+
+// Names are NUL terminated, unless they take up all 32 bytes.
+fn name_at(d: &[u8], pos: usize) -> Result<&str, FmapError> {
+    let name = &d[pos..pos + FMAP_STRLEN];
+    let len = name.iter().position(|&c| c == 0).unwrap_or(FMAP_STRLEN);
+    core::str::from_utf8(&name[..len]).map_err(|_| FmapError::BadName)
+}
+
+impl<'a> Fmap<'a> {
+    /// Parse an FMAP at the start of `data`.
+    pub fn new(data: &'a [u8]) -> Result<Fmap<'a>, FmapError> {
+        if data.len() < FMAP_HEADER_SIZE {
+            return Err(FmapError::Truncated);
+        }
+        if &data[..8] != FMAP_SIGNATURE {
+            return Err(FmapError::BadSignature);
+        }
+        let (ver_major, ver_minor) = (data[8], data[9]);
+        if ver_major != FMAP_VER_MAJOR {
+            return Err(FmapError::UnsupportedVersion(ver_major, ver_minor));
+        }
+        let nareas = u16_at(data, FMAP_HEADER_SIZE - 2) as usize;
+        let end = FMAP_HEADER_SIZE + nareas * FMAP_AREA_SIZE;
+        if data.len() < end {
+            return Err(FmapError::Truncated);
+        }
+        Ok(Fmap {
+            ver_major,
+            ver_minor,
+            base: u64_at(data, 10),
+            size: u32_at(data, 18),
+            name: name_at(data, 22)?,
+            areas: &data[FMAP_HEADER_SIZE..end],
+        })
+    }
+
+    pub fn areas(&self) -> impl Iterator<Item = Result<Area<'a>, FmapError>> {
+        let areas: &'a [u8] = self.areas;
+        areas.chunks_exact(FMAP_AREA_SIZE).map(|a| {
+            Ok(Area {
+                name: name_at(a, 8)?,
+                description: None,
+                offset: Some(u32_at(a, 0) as usize),
+                size: u32_at(a, 4) as usize,
+                file: None,
+            })
+        })
+    }
+
+    /// Look up an area by name. Areas with invalid names are skipped.
+    pub fn find_area(&self, name: &str) -> Option<Area<'a>> {
+        self.areas().flatten().find(|a| a.name == name)
+    }
+}
+
+/// Search `data` for an FMAP. coreboot aligns it to at least 64 bytes.
+/// Like coreboot's fmap_find, candidates that fail to parse are skipped, since
+/// the signature may also appear in code or data.
+pub fn find_fmap(data: &[u8]) -> Result<Fmap<'_>, FmapError> {
+    for pos in (0..data.len()).step_by(64) {
+        if data[pos..].starts_with(FMAP_SIGNATURE) {
+            if let Ok(fmap) = Fmap::new(&data[pos..]) {
+                return Ok(fmap);
+            }
+        }
+    }
+    Err(FmapError::BadSignature)
+}
+
+#[cfg(test)]
+extern crate std;
+
+#[cfg(test)]
+fn test_fmap(areas: &[(&str, u32, u32)]) -> std::vec::Vec<u8> {
+    fn name(n: &str) -> [u8; FMAP_STRLEN] {
+        let mut b = [0u8; FMAP_STRLEN];
+        b[..n.len()].copy_from_slice(n.as_bytes());
+        b
+    }
+    let mut d = std::vec::Vec::new();
+    d.extend_from_slice(FMAP_SIGNATURE);
+    d.extend_from_slice(&[FMAP_VER_MAJOR, 1]);
+    d.extend_from_slice(&0xff00_0000u64.to_le_bytes());
+    d.extend_from_slice(&0x0100_0000u32.to_le_bytes());
+    d.extend_from_slice(&name("FLASH"));
+    d.extend_from_slice(&(areas.len() as u16).to_le_bytes());
+    for (n, offset, size) in areas {
+        d.extend_from_slice(&offset.to_le_bytes());
+        d.extend_from_slice(&size.to_le_bytes());
+        d.extend_from_slice(&name(n));
+        d.extend_from_slice(&0u16.to_le_bytes());
+    }
+    d
+}
+
+#[test]
+fn read_fmap() {
+    let data = test_fmap(&[("FMAP", 0x1000, 0x200), ("COREBOOT", 0x2000, 0x10000)]);
+    let fmap = Fmap::new(&data).unwrap();
+    assert_eq!(fmap.base, 0xff00_0000);
+    assert_eq!(fmap.size, 0x0100_0000);
+    assert_eq!(fmap.name, "FLASH");
+    assert_eq!(fmap.areas().count(), 2);
+    assert_eq!(
+        fmap.find_area("COREBOOT"),
+        Some(Area {
+            name: "COREBOOT",
+            description: None,
+            offset: Some(0x2000),
+            size: 0x10000,
+            file: None,
+        })
+    );
+    assert_eq!(fmap.find_area("RW_MRC_CACHE"), None);
+}
+
+#[test]
+fn find_fmap_in_image() {
+    let mut data = std::vec![0xffu8; 0x1000];
+    data.extend(test_fmap(&[("BOOTBLOCK", 0, 0x1000)]));
+    let fmap = find_fmap(&data).unwrap();
+    assert_eq!(fmap.find_area("BOOTBLOCK").unwrap().size, 0x1000);
+    assert_eq!(
+        find_fmap(&data[..0x1000]).unwrap_err(),
+        FmapError::BadSignature
+    );
+}
+
+#[test]
+fn find_fmap_skips_invalid() {
+    let mut data = std::vec![0xffu8; 0x1000];
+    // a signature with an unsupported version, then one with an invalid name
+    let mut bad = test_fmap(&[]);
+    bad[8] = 2;
+    bad.resize(0x40, 0xff);
+    data.extend(bad);
+    let mut bad = test_fmap(&[]);
+    bad[22] = 0xff;
+    bad.resize(0x40, 0xff);
+    data.extend(bad);
+    data.extend(test_fmap(&[("BOOTBLOCK", 0, 0x1000)]));
+    let fmap = find_fmap(&data).unwrap();
+    assert_eq!(fmap.find_area("BOOTBLOCK").unwrap().size, 0x1000);
+    assert_eq!(
+        find_fmap(&data[..0x1080]).unwrap_err(),
+        FmapError::BadSignature
+    );
+}
+
+#[test]
+fn bad_fmap() {
+    let data = test_fmap(&[("FMAP", 0, 0x200)]);
+    assert_eq!(
+        Fmap::new(&data[..data.len() - 1]).unwrap_err(),
+        FmapError::Truncated
+    );
+    let mut bad = data.clone();
+    bad[8] = 2;
+    assert_eq!(
+        Fmap::new(&bad).unwrap_err(),
+        FmapError::UnsupportedVersion(2, 1)
+    );
+    let mut bad = data.clone();
+    bad[0] = b'_' + 1;
+    assert_eq!(Fmap::new(&bad).unwrap_err(), FmapError::BadSignature);
+}
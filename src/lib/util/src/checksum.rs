@@ -0,0 +1,88 @@
+/* SPDX-License-Identifier: GPL-2.0-only */
+//! Checksums used in firmware tables and image headers.
+
+/// CRC-32 as used by zlib, PNG and Ethernet (reflected, polynomial
+/// 0x04c11db7, initial value and final XOR 0xffffffff).
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            // 0xedb88320 is the bit reversed polynomial
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Internet checksum (RFC 1071): the one's complement of the one's
+/// complement sum of big-endian 16-bit words. An odd trailing byte is
+/// padded with zero.
+pub fn ip_checksum16(data: &[u8]) -> u16 {
+    let mut sum = 0u32;
+    for c in data.chunks(2) {
+        let word = match *c {
+            [hi, lo] => u16::from_be_bytes([hi, lo]),
+            [hi] => u16::from_be_bytes([hi, 0]),
+            _ => unreachable!(),
+        };
+        sum += word as u32;
+        // fold the carry back in so the sum cannot overflow
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Sum of all bytes, modulo 256, as used for Chrome EC messages.
+pub fn byte_sum8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{byte_sum8, crc32, ip_checksum16};
+
+    #[test]
+    fn crc32_vectors() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414f_a339
+        );
+    }
+
+    #[test]
+    fn ip_checksum16_ipv4_header() {
+        // checksum field zeroed
+        let mut hdr = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+        ];
+        assert_eq!(ip_checksum16(&hdr), 0xb861);
+        // a header including its checksum sums up to 0
+        hdr[10..12].copy_from_slice(&0xb861u16.to_be_bytes());
+        assert_eq!(ip_checksum16(&hdr), 0);
+    }
+
+    #[test]
+    fn ip_checksum16_odd_length() {
+        assert_eq!(ip_checksum16(&[0x01]), !0x0100);
+        assert_eq!(ip_checksum16(&[]), 0xffff);
+    }
+
+    #[test]
+    fn byte_sum8_wraps() {
+        assert_eq!(byte_sum8(&[]), 0);
+        assert_eq!(byte_sum8(&[1, 2, 3]), 6);
+        assert_eq!(byte_sum8(&[0xff, 0x02]), 0x01);
+        // appending the two's complement makes the sum 0
+        let data = [0x12, 0x34, 0x56];
+        let fixup = byte_sum8(&data).wrapping_neg();
+        assert_eq!(byte_sum8(&[0x12, 0x34, 0x56, fixup]), 0);
+    }
+}
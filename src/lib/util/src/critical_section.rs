@@ -0,0 +1,110 @@
+/* SPDX-License-Identifier: GPL-2.0-only */
+//! Interrupt-free critical sections for single-hart boot code.
+//!
+//! Masking interrupts on the current hart is enough to keep interrupt
+//! handlers from observing shared state halfway through an update. It does
+//! not protect against other harts/cores; use a lock for those.
+//!
+//! On RISC-V this masks machine mode interrupts (`mstatus.MIE`), so it
+//! must run in M-mode. On AArch64, IRQs and FIQs are masked via `DAIF`.
+//! Hosted builds, e.g. for tests, have no interrupts to mask and just run
+//! the closure. Other bare-metal architectures are not supported yet.
+
+/// Run `f` with interrupts masked and restore the previous state after.
+/// Sections can be nested; only the outermost one unmasks again.
+///
+/// If `f` panics, interrupts stay masked.
+#[inline]
+pub fn with<R>(f: impl FnOnce() -> R) -> R {
+    let state = arch::disable();
+    let r = f();
+    arch::restore(state);
+    r
+}
+
+#[cfg(all(
+    target_os = "none",
+    any(target_arch = "riscv32", target_arch = "riscv64")
+))]
+mod arch {
+    use core::arch::asm;
+
+    // also used as the immediate 8 below
+    const MSTATUS_MIE: usize = 1 << 3;
+
+    #[inline(always)]
+    pub fn disable() -> usize {
+        let mstatus: usize;
+        // atomically read mstatus and clear MIE
+        unsafe { asm!("csrrci {}, mstatus, 8", out(reg) mstatus) };
+        mstatus & MSTATUS_MIE
+    }
+
+    #[inline(always)]
+    pub fn restore(mie: usize) {
+        if mie != 0 {
+            unsafe { asm!("csrsi mstatus, 8") };
+        }
+    }
+}
+
+#[cfg(all(target_os = "none", target_arch = "aarch64"))]
+mod arch {
+    use core::arch::asm;
+
+    #[inline(always)]
+    pub fn disable() -> usize {
+        let daif: usize;
+        unsafe {
+            asm!("mrs {}, daif", out(reg) daif);
+            // I and F
+            asm!("msr daifset, #3");
+        }
+        daif
+    }
+
+    #[inline(always)]
+    pub fn restore(daif: usize) {
+        unsafe { asm!("msr daif, {}", in(reg) daif) };
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+mod arch {
+    #[inline(always)]
+    pub fn disable() -> usize {
+        0
+    }
+
+    #[inline(always)]
+    pub fn restore(_: usize) {}
+}
+
+#[cfg(all(
+    target_os = "none",
+    not(any(
+        target_arch = "riscv32",
+        target_arch = "riscv64",
+        target_arch = "aarch64"
+    ))
+))]
+compile_error!("critical sections are not implemented for this architecture");
+
+#[cfg(test)]
+mod tests {
+    use super::with;
+
+    #[test]
+    fn runs_closure() {
+        let mut ran = false;
+        with(|| ran = true);
+        assert!(ran);
+    }
+
+    #[test]
+    fn returns_value() {
+        assert_eq!(with(|| 42), 42);
+        let v = with(|| with(|| "nested"));
+        assert_eq!(v, "nested");
+    }
+}
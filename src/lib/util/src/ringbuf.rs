@@ -0,0 +1,184 @@
+/* SPDX-License-Identifier: GPL-2.0-only */
+//! Fixed size byte FIFO, e.g. for buffering UART input.
+
+use core::cell::UnsafeCell;
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::critical_section;
+
+struct Inner<const N: usize> {
+    buf: [u8; N],
+    // index of the oldest byte
+    head: usize,
+    len: usize,
+}
+
+/// A ring buffer of `N` bytes that can be shared between interrupt handlers
+/// and the main thread, on one or several harts. Every access runs in a
+/// critical section and holds a spin lock, so it can live in a plain `static`.
+pub struct RingBuffer<const N: usize> {
+    locked: AtomicBool,
+    inner: UnsafeCell<Inner<N>>,
+}
+
+// All access to `inner` holds `locked`, which keeps other harts (or threads
+// on the host) out. It is taken inside `critical_section::with`, so an
+// interrupt handler on the same hart can neither get in nor spin forever on
+// a lock its hart already holds. That needs a critical section that really
+// masks interrupts, or no interrupts at all as on the host.
+#[cfg(any(
+    not(target_os = "none"),
+    target_arch = "riscv32",
+    target_arch = "riscv64",
+    target_arch = "aarch64"
+))]
+unsafe impl<const N: usize> Sync for RingBuffer<N> {}
+
+impl<const N: usize> RingBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            inner: UnsafeCell::new(Inner {
+                buf: [0; N],
+                head: 0,
+                len: 0,
+            }),
+        }
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut Inner<N>) -> R) -> R {
+        critical_section::with(|| {
+            while self
+                .locked
+                .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                spin_loop();
+            }
+            let r = f(unsafe { &mut *self.inner.get() });
+            self.locked.store(false, Ordering::Release);
+            r
+        })
+    }
+
+    /// Append a byte. If the buffer is full, the byte is handed back.
+    pub fn push(&self, b: u8) -> Result<(), u8> {
+        self.with(|r| {
+            if r.len == N {
+                return Err(b);
+            }
+            r.buf[(r.head + r.len) % N] = b;
+            r.len += 1;
+            Ok(())
+        })
+    }
+
+    /// Take the oldest byte out.
+    pub fn pop(&self) -> Option<u8> {
+        self.with(|r| {
+            if r.len == 0 {
+                return None;
+            }
+            let b = r.buf[r.head];
+            r.head = (r.head + 1) % N;
+            r.len -= 1;
+            Some(b)
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.with(|r| r.len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+}
+
+impl<const N: usize> Default for RingBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RingBuffer;
+
+    #[test]
+    fn empty_and_full() {
+        let r = RingBuffer::<2>::new();
+        assert!(r.is_empty());
+        assert_eq!(r.pop(), None);
+        assert_eq!(r.push(1), Ok(()));
+        assert!(!r.is_empty());
+        assert!(!r.is_full());
+        assert_eq!(r.push(2), Ok(()));
+        assert!(r.is_full());
+        assert_eq!(r.push(3), Err(3));
+        assert_eq!(r.len(), 2);
+    }
+
+    #[test]
+    fn wraparound() {
+        let r = RingBuffer::<3>::new();
+        for i in 0..10 {
+            assert_eq!(r.push(i), Ok(()));
+            assert_eq!(r.push(i + 100), Ok(()));
+            assert_eq!(r.pop(), Some(i));
+            assert_eq!(r.pop(), Some(i + 100));
+            assert!(r.is_empty());
+        }
+    }
+
+    #[test]
+    fn fifo_order() {
+        static R: RingBuffer<4> = RingBuffer::new();
+        let mut out = [0u8; 8];
+        let mut n = 0;
+        // producer runs ahead of the consumer by up to the buffer size
+        for b in 1..=8 {
+            if R.push(b).is_err() {
+                while let Some(b) = R.pop() {
+                    out[n] = b;
+                    n += 1;
+                }
+                assert_eq!(R.push(b), Ok(()));
+            }
+        }
+        while let Some(b) = R.pop() {
+            out[n] = b;
+            n += 1;
+        }
+        assert_eq!(out, [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn threads() {
+        extern crate std;
+        static R: RingBuffer<16> = RingBuffer::new();
+        let producer = std::thread::spawn(|| {
+            for b in 0..=255u8 {
+                while R.push(b).is_err() {
+                    std::thread::yield_now();
+                }
+            }
+        });
+        for want in 0..=255u8 {
+            let b = loop {
+                match R.pop() {
+                    Some(b) => break b,
+                    None => std::thread::yield_now(),
+                }
+            };
+            assert_eq!(b, want);
+        }
+        producer.join().unwrap();
+        assert!(R.is_empty());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::ddrlib::*;
-use crate::init::{self, read32, udelay, write32};
+use crate::init::{self, read32, write32};
+use crate::timer::udelay;
 
 const FREQ_CHANGE: usize = 0x0004;
 const FREQ_CHANGE_ACK: usize = 0x0008;
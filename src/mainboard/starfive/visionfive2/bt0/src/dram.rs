@@ -1,8 +1,10 @@
 use crate::ddr_start::start;
 use crate::ddrcsr::omc_init;
 use crate::ddrphy::{train, util};
-use crate::init::{self, read32, udelay, write32};
-use crate::{pac, pll};
+use crate::init::{self, read32, write32};
+use crate::pll;
+use crate::syscrg::{self, SyscrgReset};
+use crate::timer::udelay;
 
 // see StarFive U-Boot drivers/ram/starfive/starfive_ddr.c
 pub fn init() {
@@ -35,76 +37,13 @@ pub fn init() {
         udelay(200);
 
         println!("[DRAM] asserts");
-        let syscrg = pac::syscrg_reg();
-
-        // DDR OSC
-        syscrg
-            .soft_rst_addr_sel_1()
-            .modify(|_, w| w.u0_ddr_osc().set_bit());
-        while syscrg
-            .syscrg_rst_status_1()
-            .read()
-            .u0_ddr_osc()
-            .bit_is_set()
-        {
-            udelay(1);
-        }
-        syscrg
-            .soft_rst_addr_sel_1()
-            .modify(|_, w| w.u0_ddr_osc().clear_bit());
-        while syscrg
-            .syscrg_rst_status_1()
-            .read()
-            .u0_ddr_osc()
-            .bit_is_clear()
-        {
-            udelay(1);
-        }
-        // DDR APB
-        syscrg
-            .soft_rst_addr_sel_1()
-            .modify(|_, w| w.u0_ddr_apb().set_bit());
-        while syscrg
-            .syscrg_rst_status_1()
-            .read()
-            .u0_ddr_apb()
-            .bit_is_set()
-        {
-            udelay(1);
-        }
-        syscrg
-            .soft_rst_addr_sel_1()
-            .modify(|_, w| w.u0_ddr_apb().clear_bit());
-        while syscrg
-            .syscrg_rst_status_1()
-            .read()
-            .u0_ddr_apb()
-            .bit_is_clear()
-        {
-            udelay(1);
-        }
-        // DDR AXI
-        syscrg
-            .soft_rst_addr_sel_1()
-            .modify(|_, w| w.u0_ddr_axi().set_bit());
-        while syscrg
-            .syscrg_rst_status_1()
-            .read()
-            .u0_ddr_axi()
-            .bit_is_set()
-        {
-            udelay(1);
-        }
-        syscrg
-            .soft_rst_addr_sel_1()
-            .modify(|_, w| w.u0_ddr_axi().clear_bit());
-        while syscrg
-            .syscrg_rst_status_1()
-            .read()
-            .u0_ddr_axi()
-            .bit_is_clear()
-        {
-            udelay(1);
+        for r in [
+            SyscrgReset::DdrOsc,
+            SyscrgReset::DdrApb,
+            SyscrgReset::DdrAxi,
+        ] {
+            syscrg::assert_reset(r);
+            syscrg::deassert_reset(r);
         }
 
         // inlined from ddr_setup()
@@ -28,7 +28,11 @@ mod ddrphy;
 mod dram;
 mod init;
 mod pac;
+mod pinctrl;
+mod plic;
 mod pll;
+mod syscrg;
+mod timer;
 mod uart;
 
 pub type EntryPoint = unsafe extern "C" fn(r0: usize, dtb: usize);
@@ -218,10 +222,7 @@ fn main() {
     });
 
     // TX/RX are GPIOs 5 and 6
-    pac::sys_pinctrl_reg().gpo_doen_1().modify(|_, w| {
-        w.doen_5().variant(0);
-        w.doen_6().variant(0)
-    });
+    pinctrl::uart0();
 
     let mut s = uart::JH71XXSerial::new();
     init_logger(s);
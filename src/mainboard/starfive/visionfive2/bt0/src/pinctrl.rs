@@ -0,0 +1,126 @@
+// SYS IOMUX: GPIO signal routing and pad configuration
+// see JH7110 TRM "System IOMUX" and Linux pinctrl-starfive-jh7110{,-sys}.c
+use crate::init::{read32, write32};
+
+const SYS_IOMUX_BASE: usize = 0x1304_0000;
+// Output enable, output signal and input signal selection. Each register
+// holds four 8-bit lanes, one per GPIO (DOEN, DOUT) or input signal (GPI).
+const DOEN_BASE: usize = SYS_IOMUX_BASE;
+const DOUT_BASE: usize = SYS_IOMUX_BASE + 0x40;
+const GPI_BASE: usize = SYS_IOMUX_BASE + 0x80;
+const DOEN_MASK: u32 = 0x3f;
+const DOUT_MASK: u32 = 0x7f;
+const GPI_MASK: u32 = 0x7f;
+// one register per pad
+const PADCFG_BASE: usize = SYS_IOMUX_BASE + 0x120;
+
+const PADCFG_IE: u32 = 1 << 0;
+const PADCFG_PU: u32 = 1 << 3;
+const PADCFG_PD: u32 = 1 << 4;
+
+pub const NUM_GPIOS: u32 = 64;
+
+// DOEN signals, the output enable is active low
+pub const GPOEN_ENABLE: u32 = 0;
+pub const GPOEN_DISABLE: u32 = 1;
+
+// DOUT signals
+pub const GPOUT_LOW: u32 = 0;
+pub const GPOUT_HIGH: u32 = 1;
+pub const GPOUT_SYS_UART0_TX: u32 = 20;
+
+// GPI signals
+pub const GPI_SYS_UART0_RX: u32 = 14;
+
+// on the VisionFive 2 40-pin header
+pub const UART0_TX_PIN: u32 = 5;
+pub const UART0_RX_PIN: u32 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pull {
+    None,
+    Up,
+    Down,
+}
+
+/// Register address and bit shift of the 8-bit lane for `idx`.
+const fn lane(base: usize, idx: u32) -> (usize, u32) {
+    (base + 4 * (idx as usize / 4), 8 * (idx % 4))
+}
+
+// The GPI lanes hold the GPIO number plus 2; 0 and 1 are fixed low and high.
+const fn gpi_val(pin: u32) -> u32 {
+    pin + 2
+}
+
+const fn padcfg_reg(pin: u32) -> usize {
+    PADCFG_BASE + 4 * pin as usize
+}
+
+/// Replace the lane for `idx` in the register value `old` with `val`.
+const fn lane_update(old: u32, idx: u32, mask: u32, val: u32) -> u32 {
+    let shift = 8 * (idx % 4);
+    (old & !(mask << shift)) | ((val & mask) << shift)
+}
+
+const _: () = assert!(lane(DOEN_BASE, UART0_TX_PIN).0 == 0x1304_0004);
+const _: () = assert!(lane(DOEN_BASE, UART0_TX_PIN).1 == 8);
+const _: () = assert!(lane(DOUT_BASE, UART0_TX_PIN).0 == 0x1304_0044);
+const _: () = assert!(lane(DOEN_BASE, UART0_RX_PIN).1 == 16);
+const _: () = assert!(lane(GPI_BASE, GPI_SYS_UART0_RX).0 == 0x1304_008c);
+const _: () = assert!(lane(GPI_BASE, GPI_SYS_UART0_RX).1 == 16);
+const _: () = assert!(gpi_val(UART0_RX_PIN) == 8);
+const _: () = assert!(padcfg_reg(UART0_RX_PIN) == 0x1304_0138);
+// UART0 TX: output signal in lane 1, output enabled
+const _: () = assert!(lane_update(0, UART0_TX_PIN, DOUT_MASK, GPOUT_SYS_UART0_TX) == 20 << 8);
+const _: () =
+    assert!(lane_update(0xffff_ffff, UART0_TX_PIN, DOEN_MASK, GPOEN_ENABLE) == 0xffff_c0ff);
+// UART0 RX: output disabled in lane 2, GPIO 6 routed to input signal 14
+const _: () = assert!(lane_update(0, UART0_RX_PIN, DOEN_MASK, GPOEN_DISABLE) == 1 << 16);
+const _: () = assert!(lane_update(0, GPI_SYS_UART0_RX, GPI_MASK, gpi_val(UART0_RX_PIN)) == 8 << 16);
+// other lanes are left alone
+const _: () = assert!(lane_update(0x4433_2211, UART0_RX_PIN, GPI_MASK, 8) == 0x4408_2211);
+
+fn write_lane(base: usize, idx: u32, mask: u32, val: u32) {
+    let (reg, _) = lane(base, idx);
+    write32(reg, lane_update(read32(reg), idx, mask, val));
+}
+
+fn modify_padcfg(pin: u32, clear: u32, set: u32) {
+    let reg = padcfg_reg(pin);
+    write32(reg, (read32(reg) & !clear) | set);
+}
+
+/// Route an output signal to a GPIO and select its output enable signal.
+pub fn set_function(pin: u32, dout: u32, doen: u32) {
+    write_lane(DOUT_BASE, pin, DOUT_MASK, dout);
+    write_lane(DOEN_BASE, pin, DOEN_MASK, doen);
+}
+
+/// Drive a GPIO from an output signal, e.g. a peripheral's TX line.
+pub fn set_gpio_output(pin: u32, dout: u32) {
+    set_function(pin, dout, GPOEN_ENABLE);
+}
+
+/// Make a GPIO an input and feed it to the input signal `gpi`.
+pub fn set_gpio_input(pin: u32, gpi: u32) {
+    set_function(pin, GPOUT_LOW, GPOEN_DISABLE);
+    modify_padcfg(pin, 0, PADCFG_IE);
+    write_lane(GPI_BASE, gpi, GPI_MASK, gpi_val(pin));
+}
+
+pub fn set_pull(pin: u32, pull: Pull) {
+    let set = match pull {
+        Pull::None => 0,
+        Pull::Up => PADCFG_PU,
+        Pull::Down => PADCFG_PD,
+    };
+    modify_padcfg(pin, PADCFG_PU | PADCFG_PD, set);
+}
+
+/// Mux UART0 onto its default pins, see U-Boot board/starfive/visionfive2/spl.c
+pub fn uart0() {
+    set_gpio_output(UART0_TX_PIN, GPOUT_SYS_UART0_TX);
+    set_gpio_input(UART0_RX_PIN, GPI_SYS_UART0_RX);
+    set_pull(UART0_RX_PIN, Pull::Up);
+}
@@ -0,0 +1,82 @@
+// Platform-Level Interrupt Controller
+// see https://github.com/riscv/riscv-plic-spec and the U74-MC core complex
+// manual, chapter "Platform-Level Interrupt Controller"
+use crate::init::{clear_bit, read32, set_bit, write32};
+
+const PLIC_BASE: usize = 0x0c00_0000;
+// one 32-bit priority register per interrupt source, source 0 is reserved
+const PRIORITY_BASE: usize = PLIC_BASE;
+// one bit per source, 0x80 bytes per context
+const ENABLE_BASE: usize = PLIC_BASE + 0x2000;
+const ENABLE_STRIDE: usize = 0x80;
+// threshold and claim/complete, 0x1000 bytes per context
+const CONTEXT_BASE: usize = PLIC_BASE + 0x20_0000;
+const CONTEXT_STRIDE: usize = 0x1000;
+const CONTEXT_THRESHOLD: usize = 0x0;
+const CONTEXT_CLAIM: usize = 0x4;
+
+// see `riscv,ndev` in the JH7110 device tree
+pub const NUM_IRQS: u32 = 136;
+pub const MAX_PRIORITY: u32 = 7;
+
+/// Machine mode context of a hart.
+/// Hart 0 is the S7 monitor core, which only has an M-mode context. The U74
+/// cores 1-4 each have an M-mode and an S-mode context, in that order.
+const fn context(hart: usize) -> usize {
+    if hart == 0 {
+        0
+    } else {
+        2 * hart - 1
+    }
+}
+
+const fn priority_reg(irq: u32) -> usize {
+    PRIORITY_BASE + 4 * irq as usize
+}
+
+const fn enable_reg(hart: usize, irq: u32) -> usize {
+    ENABLE_BASE + ENABLE_STRIDE * context(hart) + 4 * (irq as usize / 32)
+}
+
+const fn context_reg(hart: usize, offset: usize) -> usize {
+    CONTEXT_BASE + CONTEXT_STRIDE * context(hart) + offset
+}
+
+const _: () = assert!(priority_reg(10) == 0x0c00_0028);
+const _: () = assert!(enable_reg(0, 31) == 0x0c00_2000);
+const _: () = assert!(enable_reg(1, 33) == 0x0c00_2084);
+const _: () = assert!(context_reg(2, CONTEXT_CLAIM) == 0x0c20_3004);
+
+/// Set the priority of an interrupt source; 0 means never interrupt.
+pub fn set_priority(irq: u32, prio: u32) {
+    write32(priority_reg(irq), prio.min(MAX_PRIORITY));
+}
+
+/// Only interrupts with a priority above the threshold reach the hart.
+pub fn set_threshold(hart: usize, threshold: u32) {
+    write32(
+        context_reg(hart, CONTEXT_THRESHOLD),
+        threshold.min(MAX_PRIORITY),
+    );
+}
+
+pub fn enable(hart: usize, irq: u32) {
+    set_bit(enable_reg(hart, irq), irq % 32);
+}
+
+pub fn disable(hart: usize, irq: u32) {
+    clear_bit(enable_reg(hart, irq), irq % 32);
+}
+
+/// Claim the highest priority pending interrupt, if any.
+pub fn claim(hart: usize) -> Option<u32> {
+    match read32(context_reg(hart, CONTEXT_CLAIM)) {
+        0 => None,
+        irq => Some(irq),
+    }
+}
+
+/// Signal that handling a claimed interrupt is done.
+pub fn complete(hart: usize, irq: u32) {
+    write32(context_reg(hart, CONTEXT_CLAIM), irq);
+}
@@ -0,0 +1,103 @@
+// SYSCRG clock gating and software resets
+// see JH7110 TRM "System CRG" and Linux dt-bindings/clock/starfive,jh7110-crg.h
+// NOTE: The IDs below are the Linux/TRM numbering, which maps directly to
+// the register layout.
+use crate::init::{clear_bit, read32, set_bit, SYS_CRG_BASE};
+use crate::timer::udelay;
+
+// Each clock has its own 32-bit register at SYS_CRG_BASE + 4 * ID.
+// ICG = Integrated Clock Gating
+const CLK_ICG_BIT: u32 = 31;
+
+// Resets are grouped 32 per register, with a status register per group.
+// A status bit reads 1 when the reset is deasserted.
+const RESET_ASSERT0: usize = SYS_CRG_BASE + 0x02f8;
+const RESET_STATUS0: usize = SYS_CRG_BASE + 0x0308;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum SyscrgClk {
+    DdrAxi = 44,
+    I2c0Apb = 138,
+    I2c1Apb = 139,
+    I2c2Apb = 140,
+    I2c3Apb = 141,
+    I2c4Apb = 142,
+    I2c5Apb = 143,
+    I2c6Apb = 144,
+    Uart0Apb = 145,
+    Uart0Core = 146,
+}
+
+impl SyscrgClk {
+    /// Address of the clock's control register.
+    pub const fn reg(self) -> usize {
+        SYS_CRG_BASE + 4 * self as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum SyscrgReset {
+    DdrAxi = 38,
+    DdrOsc = 39,
+    DdrApb = 40,
+    I2c0Apb = 76,
+    I2c1Apb = 77,
+    I2c2Apb = 78,
+    I2c3Apb = 79,
+    I2c4Apb = 80,
+    I2c5Apb = 81,
+    I2c6Apb = 82,
+    Uart0Apb = 83,
+    Uart0Core = 84,
+}
+
+impl SyscrgReset {
+    /// Address of the assert register holding this reset.
+    pub const fn assert_reg(self) -> usize {
+        RESET_ASSERT0 + 4 * (self as usize / 32)
+    }
+
+    /// Address of the status register holding this reset.
+    pub const fn status_reg(self) -> usize {
+        RESET_STATUS0 + 4 * (self as usize / 32)
+    }
+
+    /// Bit position within the assert and status registers.
+    pub const fn bit(self) -> u32 {
+        self as u32 % 32
+    }
+}
+
+// cross-check against the constants used before these helpers existed
+const _: () = assert!(SyscrgClk::DdrAxi.reg() == crate::init::CLK_U0_DDR_AXI);
+const _: () = assert!(SyscrgReset::DdrOsc.assert_reg() == crate::init::SYS_CRG_RESET_ASSERT1);
+const _: () = assert!(SyscrgReset::DdrOsc.status_reg() == crate::init::SYS_CRG_RESET_STATUS1);
+const _: () = assert!(SyscrgReset::DdrAxi.bit() == crate::init::RSTN_U0_DDR_AXI);
+const _: () = assert!(SyscrgReset::DdrOsc.bit() == crate::init::RSTN_U0_DDR_OSC);
+const _: () = assert!(SyscrgReset::DdrApb.bit() == crate::init::RSTN_U0_DDR_APB);
+
+pub fn clk_enable(clk: SyscrgClk) {
+    set_bit(clk.reg(), CLK_ICG_BIT);
+}
+
+pub fn clk_disable(clk: SyscrgClk) {
+    clear_bit(clk.reg(), CLK_ICG_BIT);
+}
+
+/// Put a block into reset and wait until the status reflects it.
+pub fn assert_reset(r: SyscrgReset) {
+    set_bit(r.assert_reg(), r.bit());
+    while read32(r.status_reg()) & (1 << r.bit()) != 0 {
+        udelay(1);
+    }
+}
+
+/// Take a block out of reset and wait until the status reflects it.
+pub fn deassert_reset(r: SyscrgReset) {
+    clear_bit(r.assert_reg(), r.bit());
+    while read32(r.status_reg()) & (1 << r.bit()) == 0 {
+        udelay(1);
+    }
+}
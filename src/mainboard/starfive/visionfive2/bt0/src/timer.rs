@@ -0,0 +1,34 @@
+use core::ptr::read_volatile;
+
+// The CLINT machine timer counts at the RTC rate, derived from the 24MHz
+// oscillator; see `timebase-frequency` in the JH7110 device tree.
+const CLINT_BASE: usize = 0x0200_0000;
+const CLINT_MTIME: usize = CLINT_BASE + 0xbff8;
+
+pub const TIMER_FREQ: u64 = 4_000_000;
+const TICKS_PER_US: u64 = TIMER_FREQ / 1_000_000;
+
+pub const fn us_to_cycles(us: u64) -> u64 {
+    us * TICKS_PER_US
+}
+
+pub const fn cycles_to_us(cycles: u64) -> u64 {
+    cycles / TICKS_PER_US
+}
+
+const _: () = assert!(us_to_cycles(1) == 4);
+const _: () = assert!(cycles_to_us(4_000_000) == 1_000_000);
+
+/// Read the free-running 64-bit `mtime` counter.
+pub fn get_cycles() -> u64 {
+    unsafe { read_volatile(CLINT_MTIME as *const u64) }
+}
+
+/// Busy-wait for at least `us` microseconds.
+pub fn udelay(us: u32) {
+    let start = get_cycles();
+    let ticks = us_to_cycles(us as u64);
+    while get_cycles().wrapping_sub(start) < ticks {
+        core::hint::spin_loop();
+    }
+}
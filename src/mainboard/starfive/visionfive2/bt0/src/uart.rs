@@ -1,13 +1,26 @@
+use core::fmt;
+
 use log::{Error, Serial};
 
 use crate::pac;
 
 // UART0 Clock = clk_osc (24Mhz)
 const UART_CLK: u32 = 24_000_000;
-const UART_BAUDRATE_32MCLK_115200: u32 = 115200;
-const DIVISOR: u32 = UART_CLK
-    .saturating_div(16)
-    .saturating_div(UART_BAUDRATE_32MCLK_115200);
+const UART_BAUDRATE: u32 = 115200;
+
+/// Compute the 8250 divisor latch value for a given input clock and baud rate.
+/// The UART samples at 16x the baud rate; round to the nearest divisor.
+pub(crate) const fn divisor(clk: u32, baud: u32) -> u16 {
+    let d = (clk + 8 * baud) / (16 * baud);
+    if d > u16::MAX as u32 {
+        u16::MAX
+    } else {
+        d as u16
+    }
+}
+
+// 24MHz / (16 * 115200) = 13.02
+const _: () = assert!(divisor(UART_CLK, UART_BAUDRATE) == 13);
 
 pub(crate) fn uart0_divisor() -> u16 {
     let uart0 = pac::uart0_reg();
@@ -25,55 +38,85 @@ pub(crate) fn uart0_divisor() -> u16 {
     div as u16
 }
 
+/// Set up UART0 for 8N1 at the given baud rate, with FIFOs enabled and
+/// interrupts disabled.
+pub fn init(baud: u32) {
+    let uart0 = pac::uart0_reg();
+
+    /* wair for UART0 to stop being busy */
+    while uart0.usr().read().busy().bit_is_set() {}
+
+    /* set DLAB to access DLL/DLH registers */
+    uart0.lcr().modify(|_, w| w.dlab().set_bit());
+    /* NOTE: Setting the divisor requires knowing the clock. */
+    let div = divisor(UART_CLK, baud);
+    uart0.dll().write(|w| w.dll().variant(div as u8));
+    uart0.dlh().write(|w| w.dlh().variant((div >> 8) as u8));
+    /* clear the DLAB to access the other UART0 registers */
+    uart0.lcr().modify(|_, w| w.dlab().clear_bit());
+
+    /* 8 data bits, 1 stop bit, no parity */
+    uart0.lcr().modify(|_, w| {
+        w.dls().variant(0b11);
+        w.stop().clear_bit();
+        w.pen().clear_bit()
+    });
+
+    /* disable flow control */
+    uart0.mcr().modify(|_, w| w.afce().clear_bit());
+
+    /*
+     * Program FIFO: enabled, mode 0 (set for compatibility with quark),
+     * generate the interrupt at 8th byte
+     * Clear TX and RX FIFO
+     */
+    uart0.fcr().modify(|_, w| {
+        w.fifoe().set_bit();
+        w.dmam().clear_bit();
+        // Trigger on the 8th byte
+        w.rt().variant(0b10);
+        w.rfifor().set_bit();
+        w.xfifor().set_bit()
+    });
+
+    uart0.ier().modify(|_, w| w.ptime().clear_bit()); // disable the serial interrupt
+}
+
+/// Write a single byte to UART0, spinning until the transmitter is ready.
+pub fn putc(b: u8) {
+    let uart0 = pac::uart0_reg();
+    while uart0.lsr().read().thre().bit_is_clear() {}
+    uart0.thr().write(|w| w.thr().variant(b));
+}
+
+/// Write a string to UART0, injecting a carriage return before each newline.
+pub fn puts(s: &str) {
+    for b in s.bytes() {
+        if b == b'\n' {
+            putc(b'\r');
+        }
+        putc(b);
+    }
+}
+
 #[derive(Debug)]
 pub struct JH71XXSerial();
 
 impl JH71XXSerial {
     #[inline]
     pub fn new() -> Self {
-        let uart0 = pac::uart0_reg();
-
-        /* wair for UART0 to stop being busy */
-        while uart0.usr().read().busy().bit_is_set() {}
-
-        /* set DLAB to access DLL/DLH registers */
-        uart0.lcr().modify(|_, w| w.dlab().set_bit());
-        /* NOTE: Setting the divisor requires knowing the clock. */
-        uart0.dll().write(|w| w.dll().variant(DIVISOR as u8));
-        uart0.dlh().write(|w| w.dlh().variant((DIVISOR >> 8) as u8));
-        /* clear the DLAB to access the other UART0 registers */
-        uart0.lcr().modify(|_, w| w.dlab().clear_bit());
-
-        /* 8 data bits, 1 stop bit, no parity */
-        uart0.lcr().modify(|_, w| {
-            w.dls().variant(0b11);
-            w.stop().clear_bit();
-            w.pen().clear_bit()
-        });
-
-        /* disable flow control */
-        uart0.mcr().modify(|_, w| w.afce().clear_bit());
-
-        /*
-         * Program FIFO: enabled, mode 0 (set for compatibility with quark),
-         * generate the interrupt at 8th byte
-         * Clear TX and RX FIFO
-         */
-        uart0.fcr().modify(|_, w| {
-            w.fifoe().set_bit();
-            w.dmam().clear_bit();
-            // Trigger on the 8th byte
-            w.rt().variant(0b10);
-            w.rfifor().set_bit();
-            w.xfifor().set_bit()
-        });
-
-        uart0.ier().modify(|_, w| w.ptime().clear_bit()); // disable the serial interrupt
-
+        init(UART_BAUDRATE);
         Self()
     }
 }
 
+impl fmt::Write for JH71XXSerial {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        puts(s);
+        Ok(())
+    }
+}
+
 impl Serial for JH71XXSerial {}
 
 impl embedded_hal_nb::serial::ErrorType for JH71XXSerial {
@@ -89,6 +89,23 @@ struct Env {
     payload: Option<String>,
     #[clap(long, global = true, help = "Path to dtb")]
     dtb: Option<String>,
+    #[clap(long, global = true, help = "Block device to flash the image to")]
+    device: Option<String>,
+    #[clap(long, global = true, help = "Serial port to boot the image over")]
+    port: Option<String>,
+    #[clap(
+        long = "dry-run",
+        global = true,
+        help = "Print what flashing would do instead of doing it",
+        long_help = None,
+    )]
+    dry_run: bool,
+    #[clap(
+        long,
+        global = true,
+        help = "Also convert the stages to this format: bin, hex or srec"
+    )]
+    format: Option<util::ObjFormat>,
 }
 
 fn main() {
@@ -200,6 +217,23 @@ fn layout_flash(dir: &Path, path: &Path, areas: Vec<Area>) -> io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn parse_flash_args() {
+    let args =
+        Cli::try_parse_from(["xtask", "flash", "--device", "/dev/sdz", "--dry-run"]).unwrap();
+    assert!(matches!(args.command, Commands::Flash));
+    assert_eq!(args.env.device.as_deref(), Some("/dev/sdz"));
+    assert_eq!(args.env.port, None);
+    assert!(args.env.dry_run);
+
+    // global flags may also come before the subcommand
+    let args = Cli::try_parse_from(["xtask", "--port", "/dev/ttyUSB1", "flash"]).unwrap();
+    assert!(matches!(args.command, Commands::Flash));
+    assert_eq!(args.env.device, None);
+    assert_eq!(args.env.port.as_deref(), Some("/dev/ttyUSB1"));
+    assert!(!args.env.dry_run);
+}
+
 #[test]
 fn read_create() {
     static DATA: &'static [u8] = include_bytes!("testdata/test.dtb");
@@ -209,6 +243,7 @@ fn read_create() {
         8,
         Area {
             name: "",
+            description: None,
             offset: None,
             size: 0,
             file: None,
@@ -218,48 +253,56 @@ fn read_create() {
     let want: Vec<Area> = vec![
         Area {
             name: "area@0",
+            description: Some("Boot Blob"),
             offset: Some(0),
             size: 524288,
             file: None,
         },
         Area {
             name: "area@1",
+            description: Some("Fixed DTFS"),
             offset: Some(524288),
             size: 524288,
             file: Some("src/testdata/test.dtb"),
         },
         Area {
             name: "area@2",
+            description: Some("NVRAM A"),
             offset: Some(1048576),
             size: 524288,
             file: None,
         },
         Area {
             name: "area@3",
+            description: Some("NVRAM B"),
             offset: Some(1572864),
             size: 524288,
             file: None,
         },
         Area {
             name: "area@4",
+            description: Some("RomPayload DTFS A"),
             offset: Some(2097152),
             size: 1048576,
             file: None,
         },
         Area {
             name: "area@5",
+            description: Some("RomPayload DTFS B"),
             offset: Some(3145728),
             size: 1048576,
             file: None,
         },
         Area {
             name: "area@6",
+            description: Some("RamPayload DTFS A"),
             offset: Some(4194304),
             size: 6291456,
             file: None,
         },
         Area {
             name: "area@7",
+            description: Some("RamPayload DTFS B"),
             offset: Some(10485760),
             size: 6291456,
             file: None,
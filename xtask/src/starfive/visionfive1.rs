@@ -1,15 +1,11 @@
-use crate::util::{
-    compile_board_dt, dist_dir, find_binutils_prefix_or_fail, get_cargo_cmd_in, objcopy,
-    project_root,
-};
+use crate::util::{board_project_root, build_stages, compile_board_dt, dist_dir, StageSpec};
 use crate::{layout_flash, Commands, Env};
 // use fdt;
-use log::{error, info, trace};
+use log::{error, info};
 use std::{
     fs::{self, File},
     io::{self, Seek, SeekFrom},
     path::Path,
-    process,
 };
 
 extern crate layoutflash;
@@ -18,6 +14,8 @@ use layoutflash::areas::{create_areas, Area};
 // const SRAM0_SIZE = 128 * 1024;
 const SRAM0_SIZE: u64 = 32 * 1024;
 
+const BOARD_DIR: &str = "starfive/visionfive1";
+
 const ARCH: &str = "riscv64";
 const TARGET: &str = "riscv64imac-unknown-none-elf";
 
@@ -37,16 +35,27 @@ pub(crate) fn execute_command(args: &crate::Cli, features: Vec<String>) {
     match args.command {
         Commands::Make => {
             info!("building VisionFive1");
-            let binutils_prefix = &find_binutils_prefix_or_fail(ARCH);
-            // Build the stages - should we parallelize this?
-            xtask_build_jh7100_flash_bt0(&args.env, &features);
-            xtask_build_jh7100_flash_main(&args.env);
-
-            objcopy(&args.env, binutils_prefix, TARGET, ARCH, BT0_ELF, BT0_BIN);
-            objcopy(&args.env, binutils_prefix, TARGET, ARCH, MAIN_ELF, MAIN_BIN);
+            let stages = [
+                StageSpec {
+                    dir: "bt0",
+                    features: &features,
+                    extra_features: &[],
+                    elf: BT0_ELF,
+                    bin: BT0_BIN,
+                },
+                StageSpec {
+                    dir: "main",
+                    features: &[],
+                    extra_features: &[],
+                    elf: MAIN_ELF,
+                    bin: MAIN_BIN,
+                },
+            ];
+            let root = board_project_root(BOARD_DIR);
+            build_stages(&args.env, &root, &stages, TARGET, ARCH);
             xtask_concat_flash_binaries(&args.env);
             // dtb
-            compile_board_dt(&args.env, TARGET, &board_project_root(), BOARD_DTB);
+            compile_board_dt(&args.env, TARGET, &root, BOARD_DTB);
             xtask_build_dtb_image(&args.env);
         }
         _ => {
@@ -55,36 +64,6 @@ pub(crate) fn execute_command(args: &crate::Cli, features: Vec<String>) {
     }
 }
 
-fn xtask_build_jh7100_flash_bt0(env: &Env, features: &Vec<String>) {
-    trace!("build JH7100 flash bt0");
-    let mut command = get_cargo_cmd_in(env, board_project_root(), "bt0", "build");
-    if features.len() != 0 {
-        let command_line_features = features.join(",");
-        trace!("append command line features: {command_line_features}");
-        command.arg("--no-default-features");
-        command.args(&["--features", &command_line_features]);
-    } else {
-        trace!("no command line features appended");
-    }
-    let status = command.status().unwrap();
-    trace!("cargo returned {status}");
-    if !status.success() {
-        error!("cargo build failed with {status}");
-        process::exit(1);
-    }
-}
-
-fn xtask_build_jh7100_flash_main(env: &Env) {
-    trace!("build JH7100 flash main");
-    let mut command = get_cargo_cmd_in(env, board_project_root(), "main", "build");
-    let status = command.status().unwrap();
-    trace!("cargo returned {}", status);
-    if !status.success() {
-        error!("cargo build failed with {}", status);
-        process::exit(1);
-    }
-}
-
 fn xtask_concat_flash_binaries(env: &Env) {
     let dist_dir = dist_dir(env, TARGET);
     let mut bt0_file = File::options()
@@ -136,6 +115,7 @@ fn xtask_build_dtb_image(env: &Env) {
         16,
         Area {
             name: "",
+            description: None,
             offset: None,
             size: 0,
             file: None,
@@ -152,8 +132,3 @@ fn xtask_build_dtb_image(env: &Env) {
     println!("======= DONE =======");
     println!("Output file: {:?}", &output_file_path.into_os_string());
 }
-
-// FIXME: factor out, rework, share!
-fn board_project_root() -> std::path::PathBuf {
-    project_root().join("src/mainboard/starfive/visionfive1")
-}
@@ -1,23 +1,27 @@
-use crate::util::{
-    compile_board_dt, dist_dir, find_binutils_prefix_or_fail, get_cargo_cmd_in, objcopy,
-    project_root,
-};
+use crate::util::{board_project_root, build_stages, compile_board_dt, dist_dir, StageSpec};
 use crate::{layout_flash, Cli, Commands, Env};
 // use fdt;
 use log::{error, info, trace, warn};
 use std::{
     fs::{self, File},
     io::{self, Seek, SeekFrom},
-    path::Path,
-    process,
+    os::unix::fs::{FileTypeExt, MetadataExt},
+    path::{Path, PathBuf},
+    process::{self, Command},
 };
 
 extern crate layoutflash;
-use layoutflash::areas::{create_areas, Area};
+use layoutflash::areas::{create_areas, find_area_by_description, Area};
 
-use super::visionfive2_hdr::spl_create_hdr;
+use super::visionfive2_hdr::{spl_create_hdr, spl_verify_hdr};
 
 const HEADER_SIZE: usize = 0x400;
+// description of the area in board.dts reserving space for the SPL header
+const HEADER_AREA: &str = "header";
+// 16 MiB QSPI NOR flash on the VisionFive 2
+const FLASH_SIZE: usize = 16 * 1024 * 1024;
+
+const BOARD_DIR: &str = "starfive/visionfive2";
 
 const ARCH: &str = "riscv64";
 const TARGET: &str = "riscv64imac-unknown-none-elf";
@@ -34,22 +38,24 @@ const DTFS_IMAGE: &str = "starfive-visionfive2-dtfs.bin";
 
 const IMAGE: &str = "starfive-visionfive2.bin";
 
+// https://github.com/starfive-tech/Tools/tree/master/recovery
+const RECOVERY_CMD: &str = "jh7110-recovery";
+const RECOVERY_PORT: &str = "/dev/ttyUSB0";
+
 pub(crate) fn execute_command(args: &Cli, features: Vec<String>) {
     match args.command {
         Commands::Make => {
             info!("building VisionFive2");
-            // Get binutils first so we can fail early
-            let binutils_prefix = &find_binutils_prefix_or_fail(ARCH);
-            // Build the stages - should we parallelize this?
-            xtask_build_jh7110_bt0(&args.env, &features);
-            xtask_build_jh7110_main(&args.env);
-
-            objcopy(&args.env, binutils_prefix, TARGET, ARCH, BT0_ELF, BT0_BIN);
-            objcopy(&args.env, binutils_prefix, TARGET, ARCH, MAIN_ELF, MAIN_BIN);
-            // dtfs
-            compile_board_dt(&args.env, TARGET, &board_project_root(), BOARD_DTFS);
-            // final image
-            xtask_build_image(&args.env);
+            build_image(&args.env, &features);
+        }
+        Commands::Flash => {
+            info!("building and flashing VisionFive2");
+            build_image(&args.env, &features);
+            let image = dist_dir(&args.env, TARGET).join(IMAGE);
+            match &args.env.device {
+                Some(device) => flash_to_device(&image, Path::new(device), args.env.dry_run),
+                None => boot_via_recovery(&image, args.env.port.as_deref(), args.env.dry_run),
+            }
         }
         _ => {
             error!("command {:?} not implemented", args.command);
@@ -57,34 +63,28 @@ pub(crate) fn execute_command(args: &Cli, features: Vec<String>) {
     }
 }
 
-fn xtask_build_jh7110_bt0(env: &Env, features: &Vec<String>) {
-    trace!("build JH7110 bt0");
-    let mut command = get_cargo_cmd_in(env, board_project_root(), "bt0", "build");
-    if features.len() != 0 {
-        let command_line_features = features.join(",");
-        trace!("append command line features: {command_line_features}");
-        command.arg("--no-default-features");
-        command.args(&["--features", &command_line_features]);
-    } else {
-        trace!("no command line features appended");
-    }
-    let status = command.status().unwrap();
-    trace!("cargo returned {status}");
-    if !status.success() {
-        error!("cargo build failed with {status}");
-        process::exit(1);
-    }
-}
-
-fn xtask_build_jh7110_main(env: &Env) {
-    trace!("build JH7110 main");
-    let mut command = get_cargo_cmd_in(env, board_project_root(), "main", "build");
-    let status = command.status().unwrap();
-    trace!("cargo returned {status}");
-    if !status.success() {
-        error!("cargo build failed with {status}");
-        process::exit(1);
-    }
+fn build_image(env: &Env, features: &Vec<String>) {
+    let stages = [
+        StageSpec {
+            dir: "bt0",
+            features,
+            extra_features: &[],
+            elf: BT0_ELF,
+            bin: BT0_BIN,
+        },
+        StageSpec {
+            dir: "main",
+            features: &[],
+            extra_features: &[],
+            elf: MAIN_ELF,
+            bin: MAIN_BIN,
+        },
+    ];
+    build_stages(env, &board_project_root(BOARD_DIR), &stages, TARGET, ARCH);
+    // dtfs
+    compile_board_dt(env, TARGET, &board_project_root(BOARD_DIR), BOARD_DTFS);
+    // final image
+    xtask_build_image(env);
 }
 
 fn xtask_build_image(env: &Env) {
@@ -105,19 +105,36 @@ fn xtask_build_image(env: &Env) {
         16,
         Area {
             name: "",
+            description: None,
             offset: None,
             size: 0,
             file: None,
         },
     );
     let areas = create_areas(&fdt, &mut areas);
+    if let Err(e) = validate_areas(areas, HEADER_SIZE, FLASH_SIZE) {
+        error!("invalid flash layout in board DT: {e}");
+        process::exit(1);
+    }
+    // the boot ROM expects the header at the start of flash
+    let header_end = match find_area_by_description(areas, HEADER_AREA) {
+        Some((0, a)) if a.size == HEADER_SIZE => a.size,
+        _ => {
+            error!("board DT needs a {HEADER_SIZE:#x} byte area '{HEADER_AREA}' at offset 0");
+            process::exit(1);
+        }
+    };
 
     layout_flash(Path::new(&dir), Path::new(&dtfs_image_path), areas.to_vec()).unwrap();
 
     // TODO: how else do we do layoutflash + header?
     trace!("add header to {dtfs_image_path:?}");
     let dat = fs::read(dtfs_image_path).expect("DTFS image");
-    let out = spl_create_hdr(dat[HEADER_SIZE..].to_vec());
+    let out = spl_create_hdr(dat[header_end..].to_vec());
+    if let Err(e) = spl_verify_hdr(&out) {
+        error!("SPL header mismatch: {e}");
+        process::exit(1);
+    }
     let out_path = dir.join(IMAGE);
     fs::write(out_path.clone(), out).expect("writing final image");
 
@@ -125,7 +142,270 @@ fn xtask_build_image(env: &Env) {
     println!("Output file: {:?}", &out_path.into_os_string());
 }
 
-// FIXME: factor out, rework, share!
-fn board_project_root() -> std::path::PathBuf {
-    project_root().join("src/mainboard/starfive/visionfive2")
+// Check the areas the same way layout_flash places them: an area without an
+// offset follows the previous one. The first `reserved` bytes are replaced by
+// the SPL header, so no file may be placed there.
+fn validate_areas(areas: &[Area], reserved: usize, flash_size: usize) -> Result<(), String> {
+    let mut last_area_end = 0;
+    let mut last_area_name = "start of flash";
+    for a in areas {
+        // unused slots of the preallocated array
+        if a.name.is_empty() && a.size == 0 {
+            continue;
+        }
+        let offset = a.offset.unwrap_or(last_area_end);
+        if offset < last_area_end {
+            return Err(format!(
+                "area '{}' at {offset:#x} overlaps '{last_area_name}', which ends at {last_area_end:#x}",
+                a.name
+            ));
+        }
+        let end = offset + a.size;
+        if a.file.is_some() && offset < reserved {
+            return Err(format!(
+                "area '{}' at {offset:#x} is within the {reserved:#x} bytes reserved for the header",
+                a.name
+            ));
+        }
+        if end > flash_size {
+            return Err(format!(
+                "area '{}' ends at {end:#x}, beyond the flash size of {flash_size:#x}",
+                a.name
+            ));
+        }
+        last_area_end = end;
+        last_area_name = a.name;
+    }
+    if last_area_end < reserved {
+        return Err(format!(
+            "areas only cover {last_area_end:#x} bytes, less than the {reserved:#x} byte header"
+        ));
+    }
+    Ok(())
+}
+
+// Copy the image to the start of a block device, e.g. an SD card.
+fn flash_to_device(image: &Path, device: &Path, dry_run: bool) {
+    if let Err(e) = check_device(image, device) {
+        error!("refusing to write to {device:?}: {e}");
+        process::exit(1);
+    }
+    let mut command = Command::new("dd");
+    command.arg(format!("if={}", image.display()));
+    command.arg(format!("of={}", device.display()));
+    command.args(["bs=4M", "conv=fsync"]);
+    run_flash_command(command, dry_run);
+}
+
+// Upload the image over UART using the StarFive recovery tool. The board has
+// to be put into UART boot mode via the boot mode switches first. The boot ROM
+// loads the image into SRAM and runs it once; the flash is not written.
+fn boot_via_recovery(image: &Path, port: Option<&str>, dry_run: bool) {
+    let port = port.unwrap_or(RECOVERY_PORT);
+    if !Path::new(port).exists() {
+        error!("serial port {port} does not exist; use --port to select one");
+        process::exit(1);
+    }
+    warn!("no --device given, booting the image over UART once instead of flashing it");
+    let mut command = Command::new(RECOVERY_CMD);
+    command.args(["-D", port, "-r"]);
+    command.arg(image);
+    run_flash_command(command, dry_run);
+}
+
+fn run_flash_command(mut command: Command, dry_run: bool) {
+    if dry_run {
+        println!("dry run, would execute: {command:?}");
+        return;
+    }
+    trace!("execute {command:?}");
+    let status = match command.status() {
+        Ok(status) => status,
+        Err(e) => {
+            error!("could not run {:?}: {e}", command.get_program());
+            process::exit(1);
+        }
+    };
+    trace!("{:?} returned {status}", command.get_program());
+    if !status.success() {
+        error!("{:?} failed with {status}", command.get_program());
+        process::exit(1);
+    }
+}
+
+// Sanity check a target block device: it has to exist, no partition of its
+// disk may be in use by the host and it has to be large enough for the image.
+fn check_device(image: &Path, device: &Path) -> Result<(), String> {
+    let meta = fs::metadata(device).map_err(|e| format!("{e}"))?;
+    if !meta.file_type().is_block_device() {
+        return Err("not a block device".to_string());
+    }
+    let name = block_dev_name(device).ok_or("cannot resolve device name")?;
+    let in_use = in_use_disks();
+    if let Some(disk) = backing_disks(&name).iter().find(|d| in_use.contains(d)) {
+        return Err(format!("{disk} is mounted or used as swap by the host"));
+    }
+    let disk = parent_disk(&name);
+    if name != disk {
+        warn!("{device:?} is a partition of {disk}, the boot ROM may not find the image there");
+    }
+    let image_size = fs::metadata(image).map_err(|e| format!("{e}"))?.len();
+    let device_size = block_dev_size(&name).ok_or("cannot read device size")?;
+    if image_size > device_size {
+        return Err(format!(
+            "image is {image_size} bytes, but the device only has {device_size} bytes"
+        ));
+    }
+    Ok(())
+}
+
+// Kernel name of a block device, e.g. `sdb1` for `/dev/disk/by-id/...-part1`.
+fn block_dev_name(device: &Path) -> Option<String> {
+    let path = fs::canonicalize(device).ok()?;
+    Some(path.file_name()?.to_str()?.to_string())
+}
+
+// Partitions show up in sysfs as subdirectories of their disk.
+fn parent_disk(name: &str) -> String {
+    let sys = PathBuf::from("/sys/class/block").join(name);
+    if !sys.join("partition").exists() {
+        return name.to_string();
+    }
+    fs::canonicalize(&sys)
+        .ok()
+        .and_then(|p| Some(p.parent()?.file_name()?.to_str()?.to_string()))
+        .unwrap_or_else(|| name.to_string())
+}
+
+fn block_dev_size(name: &str) -> Option<u64> {
+    let size = fs::read_to_string(PathBuf::from("/sys/class/block").join(name).join("size"));
+    // sysfs always counts in 512 byte sectors
+    Some(size.ok()?.trim().parse::<u64>().ok()? * 512)
+}
+
+// Whole disks a block device is stored on. Device mapper (LUKS, LVM) and
+// MD RAID devices list the devices they are built from in `slaves/`.
+fn backing_disks(name: &str) -> Vec<String> {
+    let slaves = PathBuf::from("/sys/class/block").join(name).join("slaves");
+    let slaves: Vec<String> = fs::read_dir(slaves)
+        .map(|dir| {
+            dir.flatten()
+                .filter_map(|e| e.file_name().to_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+    if slaves.is_empty() {
+        return vec![parent_disk(name)];
+    }
+    slaves.iter().flat_map(|s| backing_disks(s)).collect()
+}
+
+// Device nodes in /proc/mounts and /proc/swaps format.
+fn used_devices<'a>(mounts: &'a str, swaps: &'a str) -> Vec<&'a str> {
+    let mounts = mounts.lines().filter_map(|l| l.split_whitespace().next());
+    // skip the header line of /proc/swaps
+    let swaps = swaps
+        .lines()
+        .skip(1)
+        .filter_map(|l| l.split_whitespace().next());
+    mounts
+        .chain(swaps)
+        .filter(|d| d.starts_with("/dev/"))
+        .collect()
+}
+
+// Disks backing any mounted file system or swap space of the host.
+fn in_use_disks() -> Vec<String> {
+    let mounts = fs::read_to_string("/proc/mounts").unwrap_or_default();
+    let swaps = fs::read_to_string("/proc/swaps").unwrap_or_default();
+    let mut names: Vec<String> = used_devices(&mounts, &swaps)
+        .into_iter()
+        .filter_map(|d| block_dev_name(Path::new(d)))
+        .collect();
+    // The root file system may be listed as /dev/root, which need not exist,
+    // so also look it up by its device number.
+    if let Some(name) = root_dev_name() {
+        names.push(name);
+    }
+    names.iter().flat_map(|n| backing_disks(n)).collect()
+}
+
+fn root_dev_name() -> Option<String> {
+    let dev = fs::metadata("/").ok()?.dev();
+    // see gnu_dev_major/gnu_dev_minor in glibc
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    block_dev_name(Path::new(&format!("/sys/dev/block/{major}:{minor}")))
+}
+
+#[test]
+fn test_used_devices() {
+    let mounts = "\
+/dev/mapper/vg-root / ext4 rw,relatime 0 0
+proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0
+/dev/nvme0n1p1 /boot vfat rw,relatime 0 0
+tmpfs /tmp tmpfs rw,nosuid,nodev 0 0
+";
+    let swaps = "\
+Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority
+/dev/sda2                               partition\t8388604\t\t0\t\t-2
+/swapfile                               file\t\t1048572\t\t0\t\t-3
+";
+    assert_eq!(
+        used_devices(mounts, swaps),
+        vec!["/dev/mapper/vg-root", "/dev/nvme0n1p1", "/dev/sda2"]
+    );
+    assert!(used_devices("", "").is_empty());
+}
+
+#[cfg(test)]
+fn test_area<'a>(name: &'a str, offset: Option<usize>, size: usize, file: bool) -> Area<'a> {
+    Area {
+        name,
+        description: Some(name),
+        offset,
+        size,
+        file: file.then_some("file.bin"),
+    }
+}
+
+#[test]
+fn test_validate_areas() {
+    let areas = [
+        test_area("header", None, HEADER_SIZE, false),
+        test_area("bt0", None, 0x10000, true),
+        test_area("main", Some(0x20000), 0x10000, true),
+        test_area("", None, 0, false),
+    ];
+    assert_eq!(validate_areas(&areas, HEADER_SIZE, FLASH_SIZE), Ok(()));
+}
+
+#[test]
+fn test_validate_areas_overlap() {
+    let areas = [
+        test_area("header", None, HEADER_SIZE, false),
+        test_area("bt0", None, 0x10000, true),
+        test_area("main", Some(0x8000), 0x10000, true),
+    ];
+    assert!(validate_areas(&areas, HEADER_SIZE, FLASH_SIZE).is_err());
+}
+
+#[test]
+fn test_validate_areas_oversized() {
+    let areas = [
+        test_area("header", None, HEADER_SIZE, false),
+        test_area("bt0", None, 0x10000, true),
+        test_area("main", None, FLASH_SIZE, true),
+    ];
+    assert!(validate_areas(&areas, HEADER_SIZE, FLASH_SIZE).is_err());
+}
+
+#[test]
+fn test_validate_areas_header() {
+    // the header area is missing, so bt0 would be cut off
+    let areas = [test_area("bt0", None, 0x10000, true)];
+    assert!(validate_areas(&areas, HEADER_SIZE, FLASH_SIZE).is_err());
+    // too small for the header
+    let areas = [test_area("header", None, 0x100, false)];
+    assert!(validate_areas(&areas, HEADER_SIZE, FLASH_SIZE).is_err());
 }
@@ -103,6 +103,73 @@ pub fn spl_create_hdr(dat: Vec<u8>) -> Vec<u8> {
     hdr
 }
 
+// Byte offsets of the fields checked by the BROM, see the layout above.
+const VERSION_OFFSET: usize = 0x284;
+const SIZE_OFFSET: usize = 0x288;
+const DATA_OFFSET_OFFSET: usize = 0x28c;
+const CRC_OFFSET: usize = 0x290;
+
+fn read_u32(img: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(img[offset..offset + 4].try_into().unwrap())
+}
+
+/// Check an image created by `spl_create_hdr`: recompute the payload size and
+/// CRC and compare them, as well as the fixed fields, against the header.
+pub fn spl_verify_hdr(img: &[u8]) -> Result<(), String> {
+    if img.len() < HEADER_SIZE as usize {
+        return Err(format!(
+            "image is {:#x} bytes, smaller than the header ({HEADER_SIZE:#x})",
+            img.len()
+        ));
+    }
+    let dat = &img[HEADER_SIZE as usize..];
+    let crc = crc32_final(crc32(CRC_IV, CRC_SV, dat.to_vec()));
+    let fields = [
+        ("spl header offset", 0, HEADER_OFFSET),
+        ("backup offset", 4, BACKUP),
+        ("version", VERSION_OFFSET, VERSION),
+        ("payload size", SIZE_OFFSET, dat.len() as u32),
+        ("payload offset", DATA_OFFSET_OFFSET, HEADER_SIZE),
+        ("payload CRC", CRC_OFFSET, crc),
+    ];
+    let mut errors = vec![];
+    for (name, offset, want) in fields {
+        let got = read_u32(img, offset);
+        if got != want {
+            errors.push(format!(
+                "{name} at {offset:#x} is {got:#x}, expected {want:#x}"
+            ));
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join(", "))
+    }
+}
+
+#[test]
+fn test_verify_hdr() {
+    let out = spl_create_hdr(b"123456789".to_vec());
+    assert_eq!(read_u32(&out, SIZE_OFFSET), 9);
+    // the usual CRC-32 check value
+    assert_eq!(read_u32(&out, CRC_OFFSET), 0xcbf4_3926);
+    assert_eq!(spl_verify_hdr(&out), Ok(()));
+
+    let mut corrupt = out.clone();
+    corrupt[HEADER_SIZE as usize] ^= 0xff;
+    assert!(spl_verify_hdr(&corrupt)
+        .unwrap_err()
+        .contains("payload CRC"));
+
+    let truncated = &out[..out.len() - 1];
+    let err = spl_verify_hdr(truncated).unwrap_err();
+    assert!(err.contains("payload size at 0x288"));
+    assert!(err.contains("payload CRC at 0x290"));
+
+    assert!(spl_verify_hdr(&out[..0x100]).is_err());
+}
+
 #[test]
 fn test_hdr() {
     static HOSTS: &[u8] = include_bytes!("testdata/hosts");
@@ -1,5 +1,6 @@
 use crate::util::{
-    dist_dir, find_binutils_prefix_or_fail, get_cargo_cmd_in, objcopy, objdump, project_root,
+    board_project_root, build_stages, cargo_build_stages, dist_dir, find_binutils_prefix_or_fail,
+    objdump, StageSpec,
 };
 use crate::{gdb_detect, Cli, Commands, Env, Memory};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
@@ -14,7 +15,7 @@ use oreboot_compression::OreLzss;
 
 const ARCH: &str = "riscv64";
 const TARGET: &str = "riscv64imac-unknown-none-elf";
-const BOARD_DIR: &str = "src/mainboard/sunxi/nezha";
+const BOARD_DIR: &str = "sunxi/nezha";
 
 const BT0_ELF: &str = "oreboot-nezha-bt0";
 const BT0_BIN: &str = "oreboot-nezha-bt0.bin";
@@ -69,50 +70,36 @@ pub(crate) fn execute_command(args: &Cli, features: Vec<String>) {
 }
 
 fn build_image(env: &Env, features: &Vec<String>) {
-    // Get binutils first so we can fail early
-    let binutils_prefix = &find_binutils_prefix_or_fail(ARCH);
-    // Build the stages - should we parallelize this?
-    build_d1_bt0(env, features);
-    build_d1_main(env);
-    objcopy(env, binutils_prefix, TARGET, ARCH, BT0_ELF, BT0_BIN);
-    objcopy(env, binutils_prefix, TARGET, ARCH, MAIN_ELF, MAIN_BIN);
+    let main_features: &[&str] = if env.supervisor { &["supervisor"] } else { &[] };
+    let stages = [
+        bt0_stage(features),
+        StageSpec {
+            dir: "main",
+            features: &[],
+            extra_features: main_features,
+            elf: MAIN_ELF,
+            bin: MAIN_BIN,
+        },
+    ];
+    build_stages(env, &board_project_root(BOARD_DIR), &stages, TARGET, ARCH);
     bt0_egon_header(env);
     concat_binaries(env);
 }
 
-fn build_d1_bt0(env: &Env, features: &Vec<String>) {
-    trace!("build D1 bt0");
-    let mut command = get_cargo_cmd_in(env, board_project_root(), "bt0", "build");
-    if !features.is_empty() {
-        let command_line_features = features.join(",");
-        trace!("append command line features: {command_line_features}");
-        command.arg("--no-default-features");
-        command.args(["--features", &command_line_features]);
-    } else {
-        trace!("no command line features appended");
-    }
-    trace!("run D1 bt0 build command: {command:?}");
-    let status = command.status().unwrap();
-    trace!("cargo returned {status}");
-    if !status.success() {
-        error!("cargo build failed with {status}");
-        process::exit(1);
+fn bt0_stage(features: &[String]) -> StageSpec<'_> {
+    StageSpec {
+        dir: "bt0",
+        features,
+        extra_features: &[],
+        elf: BT0_ELF,
+        bin: BT0_BIN,
     }
 }
 
-fn build_d1_main(env: &Env) {
-    trace!("build D1 main");
-    let mut command = get_cargo_cmd_in(env, board_project_root(), "main", "build");
-    if env.supervisor {
-        command.arg("--features");
-        command.arg("supervisor");
-    }
-    let status = command.status().unwrap();
-    trace!("cargo returned {status}");
-    if !status.success() {
-        error!("cargo build failed with {status}");
-        process::exit(1);
-    }
+// Only the ELF, for objdump and gdb
+fn build_d1_bt0(env: &Env, features: &[String]) {
+    let stages = [bt0_stage(features)];
+    cargo_build_stages(env, &board_project_root(BOARD_DIR), &stages);
 }
 
 const EGON_HEAD_LENGTH: u64 = 0x60;
@@ -356,7 +343,3 @@ fn xfel_find_connected_device(xfel: &str) {
     }
     info!("Found {}", String::from_utf8_lossy(&output.stdout).trim());
 }
-
-fn board_project_root() -> std::path::PathBuf {
-    project_root().join(BOARD_DIR)
-}
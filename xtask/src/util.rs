@@ -1,8 +1,10 @@
 use crate::Env;
 use log::{error, trace};
 use std::{
+    io,
     path::{Path, PathBuf},
-    process::{self, Command, Stdio},
+    process::{self, Command, ExitStatus, Stdio},
+    str::FromStr,
 };
 
 /// These utilities help find and run external commands.
@@ -23,6 +25,103 @@ pub fn get_cargo_cmd_in(env: &Env, root: PathBuf, dir: &str, command: &str) -> C
     cmd
 }
 
+/// A stage of a mainboard: a crate in a subdirectory of the board, e.g.
+/// `bt0`, and the ELF and raw binary it results in.
+pub struct StageSpec<'a> {
+    pub dir: &'a str,
+    /// Features from the command line, replacing the default features
+    pub features: &'a [String],
+    /// Features the board always needs, in addition to the others
+    pub extra_features: &'a [&'a str],
+    pub elf: &'a str,
+    pub bin: &'a str,
+}
+
+/// Build all stages of a board and convert them to raw binaries, plus the
+/// output format selected on the command line, if any.
+/// Exits if any stage fails to build.
+pub fn build_stages(env: &Env, root: &Path, stages: &[StageSpec], target: &str, arch: &str) {
+    // Get binutils first so we can fail early
+    let binutils_prefix = &find_binutils_prefix_or_fail(arch);
+    cargo_build_stages(env, root, stages);
+    for s in stages {
+        objcopy(env, binutils_prefix, target, arch, s.elf, s.bin);
+        // e.g. for external programmers; images are always made from binaries
+        if let Some(format) = env.format.filter(|f| *f != ObjFormat::Binary) {
+            let out = format!("{}.{}", s.elf, format.extension());
+            objcopy_format(env, binutils_prefix, target, arch, s.elf, &out, format);
+        }
+    }
+}
+
+/// Only run cargo for the stages, e.g. to debug the ELFs. Neither binutils nor
+/// objcopy are needed. Exits if any stage fails to build.
+pub fn cargo_build_stages(env: &Env, root: &Path, stages: &[StageSpec]) {
+    let builds = stages
+        .iter()
+        .map(|s| (s.dir, stage_cargo_cmd(env, root, s)))
+        .collect();
+    let failed = run_all(builds);
+    if !failed.is_empty() {
+        error!("cargo build failed for {}", failed.join(", "));
+        process::exit(1);
+    }
+}
+
+fn stage_cargo_cmd(env: &Env, root: &Path, stage: &StageSpec) -> Command {
+    trace!("build stage {}", stage.dir);
+    let mut command = get_cargo_cmd_in(env, root.to_path_buf(), stage.dir, "build");
+    if !stage.features.is_empty() {
+        let command_line_features = stage.features.join(",");
+        trace!("append command line features: {command_line_features}");
+        command.arg("--no-default-features");
+        command.args(["--features", &command_line_features]);
+    } else {
+        trace!("no command line features appended");
+    }
+    if !stage.extra_features.is_empty() {
+        command.args(["--features", &stage.extra_features.join(",")]);
+    }
+    command
+}
+
+/// Run all commands and return the names of those that failed, so that all
+/// failing stages are reported and not just the first one.
+/// They are spawned up front, but stages share the target directory, so
+/// cargo's build directory lock makes their builds run one after the other.
+pub fn run_all(commands: Vec<(&str, Command)>) -> Vec<&str> {
+    let children: Vec<_> = commands
+        .into_iter()
+        .map(|(name, mut command)| (name, command.spawn()))
+        .collect();
+    let results = children
+        .into_iter()
+        .map(|(name, child)| (name, child.and_then(|mut c| c.wait())))
+        .collect();
+    failed_stages(results)
+}
+
+// Collect the names of the stages whose build did not succeed.
+fn failed_stages(results: Vec<(&str, io::Result<ExitStatus>)>) -> Vec<&str> {
+    let mut failed = vec![];
+    for (stage, result) in results {
+        match result {
+            Ok(status) if status.success() => {
+                trace!("cargo returned {status} for {stage}");
+            }
+            Ok(status) => {
+                error!("cargo build of {stage} failed with {status}");
+                failed.push(stage);
+            }
+            Err(e) => {
+                error!("could not run cargo build of {stage}: {e}");
+                failed.push(stage);
+            }
+        }
+    }
+    failed
+}
+
 /// Compile the board device tree.
 pub fn compile_board_dt(env: &Env, target: &str, root: &PathBuf, dtb: &str) {
     trace!("compile board device tree {dtb}");
@@ -40,16 +139,81 @@ pub fn compile_board_dt(env: &Env, target: &str, root: &PathBuf, dtb: &str) {
     }
 }
 
+/// Output formats that objcopy can convert an ELF to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ObjFormat {
+    /// Raw binary
+    Binary,
+    /// Intel HEX
+    IHex,
+    /// Motorola S-record
+    Srec,
+}
+
+impl ObjFormat {
+    /// The BFD name to pass to objcopy's `-O`.
+    pub fn bfd_name(self) -> &'static str {
+        match self {
+            Self::Binary => "binary",
+            Self::IHex => "ihex",
+            Self::Srec => "srec",
+        }
+    }
+
+    /// The usual file extension for the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Binary => "bin",
+            Self::IHex => "hex",
+            Self::Srec => "srec",
+        }
+    }
+}
+
+impl FromStr for ObjFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bin" | "binary" => Ok(Self::Binary),
+            "hex" | "ihex" => Ok(Self::IHex),
+            "srec" => Ok(Self::Srec),
+            others => Err(format!("unknown object format {others}")),
+        }
+    }
+}
+
 /// Create a raw binary from an ELF.
 pub fn objcopy(env: &Env, prefix: &str, target: &str, arch: &str, elf_path: &str, bin_path: &str) {
-    trace!("objcopy binary, prefix: '{prefix}'");
+    objcopy_format(
+        env,
+        prefix,
+        target,
+        arch,
+        elf_path,
+        bin_path,
+        ObjFormat::Binary,
+    );
+}
+
+/// Convert an ELF to the given output format.
+pub fn objcopy_format(
+    env: &Env,
+    prefix: &str,
+    target: &str,
+    arch: &str,
+    elf_path: &str,
+    out_path: &str,
+    format: ObjFormat,
+) {
+    trace!("objcopy {format:?}, prefix: '{prefix}'");
     let dir = dist_dir(env, target);
     let mut cmd = Command::new(format!("{prefix}objcopy"));
     cmd.current_dir(dir);
     cmd.arg(elf_path);
     cmd.arg(format!("--binary-architecture={arch}"));
     cmd.arg("--strip-all");
-    cmd.args(["-O", "binary", bin_path]);
+    cmd.args(["-O", format.bfd_name(), out_path]);
     let status = cmd.status().unwrap();
     trace!("objcopy returned {status}");
     if !status.success() {
@@ -105,6 +269,11 @@ pub fn project_root() -> PathBuf {
     Path::new(d).ancestors().nth(1).unwrap().to_path_buf()
 }
 
+/// Get the directory of a mainboard, given as `vendor/board`.
+pub fn board_project_root(board: &str) -> PathBuf {
+    project_root().join("src/mainboard").join(board)
+}
+
 /// Get the target specific build output directory.
 /// Example: `$OREBOOT_ROOT/target/riscv64imac-unknown-none-elf/release
 pub fn dist_dir(env: &Env, target: &str) -> PathBuf {
@@ -112,3 +281,95 @@ pub fn dist_dir(env: &Env, target: &str) -> PathBuf {
     let mode = if env.release { "release" } else { "debug" };
     target_dir.join(mode)
 }
+
+#[test]
+fn test_failed_stages() {
+    use std::os::unix::process::ExitStatusExt;
+    let ok = || Ok(ExitStatus::from_raw(0));
+    // wait status for exit code 101, what cargo returns on build errors
+    let failure = || Ok(ExitStatus::from_raw(101 << 8));
+    let not_found = || Err(io::Error::from(io::ErrorKind::NotFound));
+
+    assert!(failed_stages(vec![("bt0", ok()), ("main", ok())]).is_empty());
+    assert_eq!(
+        failed_stages(vec![("bt0", failure()), ("main", ok())]),
+        vec!["bt0"]
+    );
+    assert_eq!(
+        failed_stages(vec![("bt0", ok()), ("main", not_found())]),
+        vec!["main"]
+    );
+    assert_eq!(
+        failed_stages(vec![("bt0", failure()), ("main", failure())]),
+        vec!["bt0", "main"]
+    );
+}
+
+#[test]
+fn test_run_all() {
+    let commands = vec![
+        ("bt0", Command::new("true")),
+        ("main", Command::new("false")),
+        ("payload", Command::new("/nonexistent/cargo")),
+        ("dtfs", Command::new("true")),
+    ];
+    assert_eq!(run_all(commands), vec!["main", "payload"]);
+    assert!(run_all(vec![]).is_empty());
+}
+
+#[test]
+fn test_board_project_root() {
+    let root = board_project_root("starfive/visionfive2");
+    assert!(root.ends_with("src/mainboard/starfive/visionfive2"));
+    assert!(root.join("board.dts").exists());
+}
+
+#[test]
+fn obj_format_args() {
+    for (flag, format, bfd) in [
+        ("bin", ObjFormat::Binary, "binary"),
+        ("binary", ObjFormat::Binary, "binary"),
+        ("hex", ObjFormat::IHex, "ihex"),
+        ("IHEX", ObjFormat::IHex, "ihex"),
+        ("srec", ObjFormat::Srec, "srec"),
+    ] {
+        assert_eq!(flag.parse::<ObjFormat>(), Ok(format));
+        assert_eq!(format.bfd_name(), bfd);
+    }
+    assert!("elf".parse::<ObjFormat>().is_err());
+}
+
+#[test]
+fn test_stage_cargo_cmd() {
+    use clap::Parser;
+    let env = crate::Cli::try_parse_from(["xtask", "make"]).unwrap().env;
+    let args = |features: &[String], extra_features: &[&str]| {
+        let stage = StageSpec {
+            dir: "main",
+            features,
+            extra_features,
+            elf: "main",
+            bin: "main.bin",
+        };
+        let cmd = stage_cargo_cmd(&env, Path::new("/board"), &stage);
+        cmd.get_args()
+            .map(|a| a.to_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(args(&[], &[]), ["build"]);
+    assert_eq!(
+        args(&[], &["supervisor"]),
+        ["build", "--features", "supervisor"]
+    );
+    assert_eq!(
+        args(&["nor".to_string()], &["supervisor"]),
+        [
+            "build",
+            "--no-default-features",
+            "--features",
+            "nor",
+            "--features",
+            "supervisor"
+        ]
+    );
+}